@@ -1,28 +1,164 @@
-use crate::math::Ray;
+use crate::math::{Ray, random_in_unit_disk};
 use crate::glam::Vec3;
+use crate::rand::{thread_rng, Rng};
+
+//What a scene describes about its camera, the Camera itself is built once the output resolution is known
+#[derive(Clone, Copy)]
+pub struct CameraSettings {
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    pub up: Vec3,
+    pub vfov: f32,
+    pub aperture: f32,
+    //None focuses on look_at
+    pub focus_distance: Option<f32>,
+    //Camera rays get a time between these, equal values turn motion blur off
+    pub shutter_open: f32,
+    pub shutter_close: f32
+}
+
+impl CameraSettings {
+    pub fn new() -> CameraSettings {
+        CameraSettings {
+            look_from: Vec3::new(0.0, 0.0, 0.0),
+            look_at: Vec3::new(0.0, 0.0, -1.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0
+        }
+    }
+
+    pub fn build(&self, aspect: f32) -> Camera {
+        let focus_distance = self.focus_distance.unwrap_or_else(|| (self.look_from - self.look_at).length());
+        Camera::new(self.look_from, self.look_at, self.up, self.vfov, aspect, self.aperture, focus_distance)
+            .with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings::new()
+    }
+}
 
 pub struct Camera {
     origin:Vec3,
     lower_left_corner:Vec3,
     vertical:Vec3,
-    horizontal:Vec3
+    horizontal:Vec3,
+    u:Vec3,
+    v:Vec3,
+    lens_radius: f32,
+    shutter_open: f32,
+    shutter_close: f32
 }
 
 impl Camera {
-    pub fn new(_vfov: f32, _aspect: f32) -> Camera {
+    //vfov is the top to bottom field of view in degrees, aspect is width over height
+    //Rays start on a lens of diameter aperture and converge on the plane focus_distance away, an aperture of 0 is a pinhole
+    pub fn new(look_from: Vec3, look_at: Vec3, up: Vec3, vfov: f32, aspect: f32, aperture: f32, focus_distance: f32) -> Camera {
+        let half_height = (vfov.to_radians() / 2.0).tan();
+        let half_width = aspect * half_height;
+
+        let w = (look_from - look_at).normalize();
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+
         Camera{
-            lower_left_corner:Vec3::new(-2.0, -1.0, -1.0),
-            horizontal:Vec3::new(2.0 * 2.0, 0.0, 0.0),
-            vertical:Vec3::new(0.0, 2.0 * 1.0, 0.0),
-            origin:Vec3::new(0.0, 0.0, 0.0)
+            lower_left_corner:look_from - (u * half_width + v * half_height + w) * focus_distance,
+            horizontal:u * 2.0 * half_width * focus_distance,
+            vertical:v * 2.0 * half_height * focus_distance,
+            origin:look_from,
+            u,
+            v,
+            lens_radius: aperture / 2.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0
+        }
+    }
+
+    pub fn with_shutter(mut self, open: f32, close: f32) -> Camera {
+        self.shutter_open = open.min(close);
+        self.shutter_close = open.max(close);
+        self
+    }
+
+    #[inline]
+    fn sample_time(&self) -> f32 {
+        if self.shutter_close == self.shutter_open {
+            return self.shutter_open;
         }
+        thread_rng().gen_range(self.shutter_open, self.shutter_close)
     }
 
     #[inline]
     pub fn get_ray(&self, u:f32, v:f32) -> Ray {
-        Ray::new(
-            self.origin,
-            self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin
+        if self.lens_radius == 0.0 {
+            return Ray::new_at_time(
+                self.origin,
+                self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin,
+                self.sample_time()
+            );
+        }
+
+        let rd = random_in_unit_disk() * self.lens_radius;
+        let offset = self.u * rd.x() + self.v * rd.y();
+        Ray::new_at_time(
+            self.origin + offset,
+            self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin - offset,
+            self.sample_time()
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //The pinhole camera from before the thin lens, the image plane one unit in front of look_from
+    fn pinhole_ray(look_from: Vec3, look_at: Vec3, up: Vec3, vfov: f32, aspect: f32, s: f32, t: f32) -> Ray {
+        let half_height = (vfov.to_radians() / 2.0).tan();
+        let half_width = aspect * half_height;
+        let w = (look_from - look_at).normalize();
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+        let lower_left_corner = look_from - u * half_width - v * half_height - w;
+        Ray::new(look_from, lower_left_corner + u * (2.0 * half_width * s) + v * (2.0 * half_height * t) - look_from)
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn zero_aperture_is_the_pinhole_camera() {
+        let (look_from, look_at, up) = (Vec3::new(3.0, 2.0, 1.0), Vec3::new(-1.0, 0.5, -4.0), Vec3::new(0.0, 1.0, 0.0));
+        for focus_distance in &[1.0, 6.5] {
+            let camera = Camera::new(look_from, look_at, up, 50.0, 1.5, 0.0, *focus_distance);
+            for (s, t) in &[(0.0, 0.0), (0.5, 0.5), (1.0, 0.25), (0.3, 1.0)] {
+                let ray = camera.get_ray(*s, *t);
+                let expected = pinhole_ray(look_from, look_at, up, 50.0, 1.5, *s, *t);
+                assert_close(ray.origin, expected.origin);
+                //The image plane sits focus_distance away instead of 1, which only scales the direction
+                assert_close(ray.direction / *focus_distance, expected.direction);
+            }
+        }
+    }
+
+    #[test]
+    fn lens_rays_meet_on_the_focus_plane() {
+        let (look_from, look_at, up) = (Vec3::new(3.0, 2.0, 1.0), Vec3::new(-1.0, 0.5, -4.0), Vec3::new(0.0, 1.0, 0.0));
+        let camera = Camera::new(look_from, look_at, up, 50.0, 1.5, 0.5, 4.0);
+        let target = pinhole_ray(look_from, look_at, up, 50.0, 1.5, 0.7, 0.2).point_at_paramater(4.0);
+        let mut spread = 0.0f32;
+        for _ in 0..100 {
+            let ray = camera.get_ray(0.7, 0.2);
+            assert_close(ray.point_at_paramater(1.0), target);
+            spread = spread.max((ray.origin - look_from).length());
+        }
+        assert!(spread > 0.0 && spread <= 0.25 + 1e-5);
+    }
+}
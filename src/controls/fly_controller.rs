@@ -0,0 +1,94 @@
+use crate::controls::CameraSettings;
+use crate::glam::{Vec3, Quat, rad};
+use crate::minifb::{Key, MouseButton, MouseMode, Window};
+
+//Radians of rotation per pixel the mouse is dragged
+const LOOK_SPEED: f32 = 0.005;
+//Keeps the view from flipping over when looking straight up or down
+const MAX_PITCH_COSINE: f32 = 0.99;
+
+//WASD or the arrow keys move, E and Q go up and down, dragging with the left mouse button looks around
+pub struct FlyController {
+    settings: CameraSettings,
+    forward: Vec3,
+    focus_distance: f32,
+    move_speed: f32,
+    last_mouse: Option<(f32, f32)>
+}
+
+impl FlyController {
+    pub fn new(settings: CameraSettings) -> FlyController {
+        let to_target = settings.look_at - settings.look_from;
+        let focus_distance = settings.focus_distance.unwrap_or_else(|| to_target.length());
+
+        FlyController {
+            settings,
+            forward: to_target.normalize(),
+            focus_distance,
+            //crossing the distance to what the camera looks at takes about two seconds
+            move_speed: to_target.length().max(0.1) * 0.5,
+            last_mouse: None
+        }
+    }
+
+    //Returns true when the camera moved, so anything accumulated from the old view can be thrown away
+    pub fn update(&mut self, window: &Window, delta_seconds: f32) -> bool {
+        let up = self.settings.up.normalize();
+        let right = self.forward.cross(up).normalize();
+        let mut moved = false;
+
+        let mut direction = Vec3::zero();
+        if window.is_key_down(Key::W) || window.is_key_down(Key::Up) {
+            direction += self.forward;
+        }
+        if window.is_key_down(Key::S) || window.is_key_down(Key::Down) {
+            direction -= self.forward;
+        }
+        if window.is_key_down(Key::D) || window.is_key_down(Key::Right) {
+            direction += right;
+        }
+        if window.is_key_down(Key::A) || window.is_key_down(Key::Left) {
+            direction -= right;
+        }
+        if window.is_key_down(Key::E) {
+            direction += up;
+        }
+        if window.is_key_down(Key::Q) {
+            direction -= up;
+        }
+
+        if direction.length() > 0.0 {
+            self.settings.look_from += direction.normalize() * self.move_speed * delta_seconds;
+            moved = true;
+        }
+
+        let mouse = window.get_mouse_pos(MouseMode::Pass);
+        if window.get_mouse_down(MouseButton::Left) {
+            if let (Some((x, y)), Some((last_x, last_y))) = (mouse, self.last_mouse) {
+                let (dx, dy) = (x - last_x, y - last_y);
+                if dx != 0.0 || dy != 0.0 {
+                    let yawed = Quat::from_axis_angle(up, rad(-dx * LOOK_SPEED)).mul_vec3(self.forward);
+                    let pitched = Quat::from_axis_angle(right, rad(-dy * LOOK_SPEED)).mul_vec3(yawed).normalize();
+                    self.forward = if pitched.dot(up).abs() < MAX_PITCH_COSINE { pitched } else { yawed.normalize() };
+                    moved = true;
+                }
+            }
+            self.last_mouse = mouse;
+        }
+        else {
+            self.last_mouse = None;
+        }
+
+        if moved {
+            self.settings.look_at = self.settings.look_from + self.forward * self.focus_distance;
+        }
+        moved
+    }
+
+    pub fn camera_settings(&self) -> CameraSettings {
+        CameraSettings {
+            focus_distance: Some(self.focus_distance),
+            ..self.settings
+        }
+    }
+}
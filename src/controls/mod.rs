@@ -1,2 +1,4 @@
 pub mod camera;
-pub use self::camera::Camera;
\ No newline at end of file
+pub mod fly_controller;
+pub use self::camera::{Camera, CameraSettings};
+pub use self::fly_controller::FlyController;
\ No newline at end of file
@@ -1,13 +1,64 @@
 pub mod ray;
+pub mod perlin;
 
 pub use self::ray::Ray;
+pub use self::perlin::Perlin;
 use crate::glam::Vec3;
 use crate::rand::{thread_rng, Rng};
 
+//Uniform over the ball, by rejection from the surrounding cube
 #[inline]
 pub fn random_in_unit_sphere() ->Vec3{
     let mut rng = thread_rng();
-   Vec3::new(rand::random::<f32>(), rand::random::<f32>(), rand::random::<f32>()).normalize() * rng.gen_range(0.0, 1.0)
+    loop {
+        let p = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+        if p.dot(p) < 1.0 {
+            return p;
+        }
+    }
+}
+
+#[inline]
+pub fn random_in_unit_disk() ->Vec3{
+    let mut rng = thread_rng();
+    loop {
+        let p = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 0.0);
+        if p.dot(p) < 1.0 {
+            return p;
+        }
+    }
+}
+
+//Direction in the z up hemisphere with density cos(theta) / pi
+#[inline]
+pub fn random_cosine_direction() -> Vec3 {
+    let mut rng = thread_rng();
+    let r1 : f32 = rng.gen_range(0.0, 1.0);
+    let r2 : f32 = rng.gen_range(0.0, 1.0);
+    let phi = 2.0 * std::f32::consts::PI * r1;
+    let r = r2.sqrt();
+    Vec3::new(phi.cos() * r, phi.sin() * r, (1.0 - r2).max(0.0).sqrt())
+}
+
+//Moves a z up local direction into the frame around the unit vector n
+#[inline]
+pub fn to_world(local: Vec3, n: Vec3) -> Vec3 {
+    let (tangent, bitangent) = orthonormal_basis(n);
+    tangent * local.x() + bitangent * local.y() + n * local.z()
+}
+
+//Two unit vectors perpendicular to n and to each other, n must be normalized
+#[inline]
+pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let helper = if n.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let tangent = helper.cross(n).normalize();
+    (tangent, n.cross(tangent))
+}
+
+//Rec. 709 weights, how bright a linear color looks
+#[inline]
+pub fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
 }
 
 #[inline]
@@ -0,0 +1,80 @@
+use crate::glam::Vec3;
+use crate::rand::{Rng, SeedableRng};
+use crate::rand::rngs::StdRng;
+
+const POINT_COUNT: usize = 256;
+
+//Gradient noise over 3D space, values fall roughly between -1 and 1.
+//The tables come from a fixed seed so the same scene always renders the same pattern.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT).map(|_| {
+            Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)).normalize()
+        }).collect();
+
+        Perlin {
+            gradients,
+            perm_x: permutation(&mut rng),
+            perm_y: permutation(&mut rng),
+            perm_z: permutation(&mut rng)
+        }
+    }
+
+    pub fn noise(&self, p: Vec3) -> f32 {
+        let floor = Vec3::new(p.x().floor(), p.y().floor(), p.z().floor());
+        let f = p - floor;
+        let (i, j, k) = (floor.x() as i32, floor.y() as i32, floor.z() as i32);
+
+        //Hermite smoothing hides the grid
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (u, v, w) = (smooth(f.x()), smooth(f.y()), smooth(f.z()));
+
+        let mut accumulated = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+                    let weight = Vec3::new(f.x() - di as f32, f.y() - dj as f32, f.z() - dk as f32);
+                    let (a, b, c) = (di as f32, dj as f32, dk as f32);
+                    accumulated += (a * u + (1.0 - a) * (1.0 - u))
+                        * (b * v + (1.0 - b) * (1.0 - v))
+                        * (c * w + (1.0 - c) * (1.0 - w))
+                        * self.gradients[index].dot(weight);
+                }
+            }
+        }
+        accumulated
+    }
+
+    //Sum of depth octaves, each twice the frequency and half the weight of the last
+    pub fn turbulence(&self, p: Vec3, depth: u32) -> f32 {
+        let mut accumulated = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accumulated += weight * self.noise(p);
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        accumulated.abs()
+    }
+}
+
+fn permutation(rng: &mut StdRng) -> Vec<usize> {
+    let mut values : Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        let target = rng.gen_range(0, i + 1);
+        values.swap(i, target);
+    }
+    values
+}
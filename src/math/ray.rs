@@ -2,14 +2,21 @@ use crate::glam::Vec3;
 
 pub struct Ray {
    pub origin:Vec3,
-   pub direction:Vec3
+   pub direction:Vec3,
+   //When during the shutter interval the ray was cast, moving objects are hit where they are at this time
+   pub time: f32
 }
 
 impl Ray {
     pub fn new(origin:Vec3, direction:Vec3) -> Ray {
+        Ray::new_at_time(origin, direction, 0.0)
+    }
+
+    pub fn new_at_time(origin:Vec3, direction:Vec3, time: f32) -> Ray {
         Ray {
             origin,
-            direction
+            direction,
+            time
         }
     }
 
@@ -0,0 +1,59 @@
+use crate::glam::Vec3;
+use crate::render::to_display_color;
+
+//Running sum of every frame rendered since the last reset, so the preview converges instead of showing one frame of noise
+pub struct AccumulationBuffer {
+    sum: Vec<Vec3>,
+    frame_count: u32,
+    samples_per_frame: u32
+}
+
+impl AccumulationBuffer {
+    pub fn new(size: usize, samples_per_frame: u32) -> AccumulationBuffer {
+        AccumulationBuffer {
+            sum: vec![Vec3::zero(); size],
+            frame_count: 0,
+            samples_per_frame
+        }
+    }
+
+    pub fn add_frame(&mut self, frame: &[Vec3]) {
+        for (sum, color) in self.sum.iter_mut().zip(frame.iter()) {
+            *sum += *color;
+        }
+        self.frame_count += 1;
+    }
+
+    //Call whenever the camera or scene changes, old samples no longer describe the image
+    pub fn reset(&mut self) {
+        for sum in self.sum.iter_mut() {
+            *sum = Vec3::zero();
+        }
+        self.frame_count = 0;
+    }
+
+    #[inline]
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.frame_count * self.samples_per_frame
+    }
+
+    pub fn average(&self) -> Vec<Vec3> {
+        let mut average = vec![Vec3::zero(); self.sum.len()];
+        self.average_into(&mut average);
+        average
+    }
+
+    pub fn average_into(&self, buffer: &mut [Vec3]) {
+        let scale = 1.0 / self.frame_count.max(1) as f32;
+        for (pixel, sum) in buffer.iter_mut().zip(self.sum.iter()) {
+            *pixel = *sum * scale;
+        }
+    }
+
+    pub fn display_into(&self, buffer: &mut [u32]) {
+        let scale = 1.0 / self.frame_count.max(1) as f32;
+        for (pixel, sum) in buffer.iter_mut().zip(self.sum.iter()) {
+            *pixel = to_display_color(*sum * scale);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::glam::Vec3;
+use crate::math::Ray;
+use crate::scene::{Scene, HitRecord, HitableID, Material, Light};
+use crate::rand::{thread_rng, Rng};
+
+use std::f32;
+
+//Bounces before a path is cut off and treated as absorbed
+pub const MAX_DEPTH: u32 = 10;
+
+//Closest a bounce or shadow ray may hit, keeps rays from finding the surface they start on
+const RAY_EPSILON: f32 = 0.001;
+
+#[inline]
+fn is_black(c: Vec3) -> bool {
+    c.x() <= 0.0 && c.y() <= 0.0 && c.z() <= 0.0
+}
+
+//Surfaces take light in by the cosine to their normal, media take it in the same from everywhere
+#[inline]
+fn cosine_term(material: &dyn Material, record: &HitRecord, wi: Vec3) -> f32 {
+    if material.is_medium() { 1.0 } else { record.normal.dot(wi).abs() }
+}
+
+//Light left after crossing distance of fog, None for light coming from outside the scene
+#[inline]
+fn fog_transmittance(scene: &Scene, distance: Option<f32>) -> f32 {
+    scene.fog.as_ref().map_or(1.0, |fog| fog.transmittance(distance))
+}
+
+#[inline]
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+//Density of sample_emitter picking direction from origin, choosing the emitter is part of it
+fn emitter_pdf(scene: &Scene, id: HitableID, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+    if !scene.emitters.contains(&id) {
+        return 0.0;
+    }
+    match scene.hitable_library.checkout_hitable(id) {
+        Some(emitter) => emitter.pdf_value(origin, direction, time) / scene.emitters.len() as f32,
+        None => 0.0
+    }
+}
+
+//Casts one shadow ray at a random emitter and weighs what it finds against the chance the BSDF would have found it
+fn sample_emitter(scene: &Scene, material: &dyn Material, record: &HitRecord, wo: Vec3, time: f32) -> Vec3 {
+    if scene.emitters.is_empty() {
+        return Vec3::zero();
+    }
+
+    let id = scene.emitters[thread_rng().gen_range(0, scene.emitters.len())];
+    let emitter = match scene.hitable_library.checkout_hitable(id) {
+        Some(emitter) => emitter,
+        None => panic!("Object {} does not exist", id)
+    };
+
+    let direction = emitter.random(record.position, time);
+    let light_pdf = emitter.pdf_value(record.position, direction, time) / scene.emitters.len() as f32;
+    if light_pdf <= 0.0 {
+        return Vec3::zero();
+    }
+
+    let wi = direction.normalize();
+    let f = material.eval(record, wo, wi);
+    let cosine = cosine_term(material, record, wi);
+    if is_black(f) || cosine <= 0.0 {
+        return Vec3::zero();
+    }
+
+    let mut shadow = HitRecord::empty();
+    let shadow_ray = Ray::new_at_time(record.position, direction, time);
+    if !scene.world.cast_ray_into_world(&shadow_ray, RAY_EPSILON, f32::MAX, &mut shadow, &scene.hitable_library) || shadow.hitable != id {
+        return Vec3::zero();
+    }
+
+    let emitted = match scene.material_library.checkout_material(shadow.material) {
+        Some(light) => light.emitted(&shadow),
+        None => panic!("Tried to unwrap a nonesistant material {}", shadow.material)
+    };
+
+    let weight = power_heuristic(light_pdf, material.pdf(record, wo, wi));
+    let transmittance = fog_transmittance(scene, Some(shadow.t * direction.length()));
+    f * emitted * (cosine * weight * transmittance / light_pdf)
+}
+
+//Shadow ray toward a bright part of the environment, weighed against the BSDF escaping the same way
+fn sample_environment(scene: &Scene, material: &dyn Material, record: &HitRecord, wo: Vec3, time: f32) -> Vec3 {
+    let (wi, light_pdf) = match scene.environment.sample() {
+        Some(sample) => sample,
+        None => return Vec3::zero()
+    };
+
+    let f = material.eval(record, wo, wi);
+    let cosine = cosine_term(material, record, wi);
+    if is_black(f) || cosine <= 0.0 {
+        return Vec3::zero();
+    }
+
+    let mut shadow = HitRecord::empty();
+    let shadow_ray = Ray::new_at_time(record.position, wi, time);
+    if scene.world.cast_ray_into_world(&shadow_ray, RAY_EPSILON, f32::MAX, &mut shadow, &scene.hitable_library) {
+        return Vec3::zero();
+    }
+
+    let weight = power_heuristic(light_pdf, material.pdf(record, wo, wi));
+    f * scene.environment.color(wi) * (cosine * weight * fog_transmittance(scene, None) / light_pdf)
+}
+
+//Every punctual light is delta like a mirror, so each is added with a shadow ray and nothing else finds them
+fn sample_lights(scene: &Scene, material: &dyn Material, record: &HitRecord, wo: Vec3, time: f32) -> Vec3 {
+    let mut total = Vec3::zero();
+    let mut shadow = HitRecord::empty();
+
+    for light in &scene.lights {
+        let (wi, distance, irradiance) = match light.illuminate(record.position) {
+            Some(incoming) => incoming,
+            None => continue
+        };
+
+        let f = material.eval(record, wo, wi);
+        let cosine = cosine_term(material, record, wi);
+        if is_black(f) || cosine <= 0.0 {
+            continue;
+        }
+
+        let shadow_ray = Ray::new_at_time(record.position, wi, time);
+        if scene.world.cast_ray_into_world(&shadow_ray, RAY_EPSILON, distance - RAY_EPSILON, &mut shadow, &scene.hitable_library) {
+            continue;
+        }
+        let reach = match light {
+            Light::Directional { .. } => None,
+            _ => Some(distance)
+        };
+        total += f * irradiance * (cosine * fog_transmittance(scene, reach));
+    }
+    total
+}
+
+//Everything reaching a scattering point straight from a light source
+fn direct_light(scene: &Scene, material: &dyn Material, record: &HitRecord, wo: Vec3, time: f32) -> Vec3 {
+    sample_emitter(scene, material, record, wo, time)
+        + sample_environment(scene, material, record, wo, time)
+        + sample_lights(scene, material, record, wo, time)
+}
+
+//Follows one path through the scene, bouncing off surfaces and scattering in media.
+//At every non specular bounce one emitter, the environment and every light are sampled directly,
+//and emitters or environment the path runs into are weighted by multiple importance sampling against that,
+//so both ways of finding a light add up to its contribution once.
+pub fn radiance(ray: Ray, scene: &Scene) -> Vec3 {
+    let mut ray = ray;
+    let mut throughput = Vec3::one();
+    let mut radiance = Vec3::zero();
+    let mut record = HitRecord::empty();
+
+    //The camera ray is treated like a mirror bounce, nothing could have sampled what it sees directly
+    let mut specular_bounce = true;
+    let mut last_pdf = 0.0;
+    let mut last_position = ray.origin;
+
+    for _ in 0..MAX_DEPTH {
+        let hit = scene.world.cast_ray_into_world(&ray, RAY_EPSILON, f32::MAX, &mut record, &scene.hitable_library);
+
+        //Fog can scatter the ray anywhere before the surface it was heading for
+        if let Some(fog) = &scene.fog {
+            let length = ray.direction.length();
+            let reach = if hit { Some(record.t * length) } else { None };
+            if let Some(travelled) = fog.scatter_distance(reach) {
+                let direction = ray.direction / length;
+                let mut medium = HitRecord::empty();
+                medium.position = ray.origin + direction * travelled;
+                medium.normal = -direction;
+                radiance += throughput * direct_light(scene, &fog.phase, &medium, -direction, ray.time);
+
+                let (scattered, pdf) = fog.phase.sample(direction);
+                throughput *= fog.phase.albedo.value(0.0, 0.0, medium.position);
+                specular_bounce = false;
+                last_pdf = pdf;
+                last_position = medium.position;
+                ray = Ray::new_at_time(medium.position, scattered, ray.time);
+                continue;
+            }
+        }
+
+        if !hit {
+            let direction = ray.get_direction().normalize();
+            let weight = if specular_bounce {
+                1.0
+            }
+            else {
+                power_heuristic(last_pdf, scene.environment.pdf(direction))
+            };
+            radiance += throughput * scene.environment.color(direction) * weight;
+            break;
+        }
+
+        let material = match scene.material_library.checkout_material(record.material) {
+            Some(material) => material,
+            None => panic!("Tried to unwrap a nonesistant material {}", record.material)
+        };
+        let object = match scene.hitable_library.checkout_hitable(record.hitable) {
+            Some(object) => object,
+            None => panic!("Object {} does not exist", record.hitable)
+        };
+
+        material.perturb_normal(&mut record);
+        let emitted = material.emitted(&record);
+        if !is_black(emitted) {
+            let weight = if specular_bounce {
+                1.0
+            }
+            else {
+                power_heuristic(last_pdf, emitter_pdf(scene, record.hitable, last_position, ray.direction, ray.time))
+            };
+            radiance += throughput * emitted * weight;
+        }
+
+        let scatter_hit = material.scatter(&ray, &record, object.as_ref());
+        if !scatter_hit.result {
+            break;
+        }
+
+        if !scatter_hit.specular {
+            let wo = -ray.get_direction().normalize();
+            radiance += throughput * direct_light(scene, material.as_ref(), &record, wo, ray.time);
+        }
+
+        throughput *= scatter_hit.attenuation;
+        specular_bounce = scatter_hit.specular;
+        last_pdf = scatter_hit.pdf;
+        last_position = record.position;
+        //Materials build their rays without a time, the whole path happens at the instant the camera ray was cast
+        ray = Ray::new_at_time(scatter_hit.scattered.origin, scatter_hit.scattered.direction, ray.time);
+    }
+
+    radiance
+}
@@ -0,0 +1,11 @@
+pub mod settings;
+pub mod output;
+pub mod renderer;
+pub mod accumulation;
+pub mod integrator;
+
+pub use self::settings::{RenderSettings, CommandLine};
+pub use self::output::{to_display_color, write_image};
+pub use self::accumulation::AccumulationBuffer;
+pub use self::integrator::radiance;
+pub use self::renderer::{render_frame, render_thread, RayTraceThreadConfig, RayTracePixelConfig};
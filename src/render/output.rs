@@ -0,0 +1,48 @@
+use crate::glam::Vec3;
+use crate::image;
+use crate::image::hdr::HDREncoder;
+use crate::image::Rgb;
+use crate::exr::prelude::{Image, SpecificChannels, Vec2, WritableImage};
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+#[inline]
+pub fn to_display_color(color: Vec3) -> u32 {
+    let (red, green, blue) = (color.min(Vec3::one()).max(Vec3::zero()) * 255.0).into();
+    (red as u32) << 16 | (green as u32) << 8 | (blue as u32)
+}
+
+//buffer is row major with the top row first, the same layout the window uses
+pub fn write_image(path: &Path, buffer: &[Vec3], width: usize, height: usize) -> io::Result<()> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+
+    match extension.as_str() {
+        "exr" => write_exr(path, buffer, width, height),
+        "hdr" => {
+            let pixels : Vec<Rgb<f32>> = buffer.iter().map(|c| Rgb([c.x(), c.y(), c.z()])).collect();
+            let file = BufWriter::new(File::create(path)?);
+            HDREncoder::new(file).encode(&pixels, width, height)
+        },
+        _ => {
+            let mut pixels = Vec::with_capacity(width * height * 3);
+            for color in buffer {
+                let color = to_display_color(*color);
+                pixels.push((color >> 16) as u8);
+                pixels.push((color >> 8) as u8);
+                pixels.push(color as u8);
+            }
+            image::save_buffer(path, &pixels, width as u32, height as u32, image::RGB(8))
+        }
+    }
+}
+
+fn write_exr(path: &Path, buffer: &[Vec3], width: usize, height: usize) -> io::Result<()> {
+    let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+        let color = buffer[y * width + x];
+        (color.x(), color.y(), color.z())
+    });
+    Image::from_channels((width, height), channels).write().to_file(path)
+        .map_err(|e| io::Error::other(e.to_string()))
+}
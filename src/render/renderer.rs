@@ -0,0 +1,76 @@
+use crate::glam::Vec3;
+use crate::rayon::prelude::*;
+use crate::rand::{thread_rng, Rng};
+
+use crate::controls::Camera;
+use crate::scene::Scene;
+use crate::render::{RenderSettings, radiance};
+
+pub struct RayTraceThreadConfig<'a> {
+    rtpc: Vec<RayTracePixelConfig<'a>>,
+    camera: &'a Camera,
+    pixel_subset: &'a mut [Vec3],
+    number_of_samples: u32
+}
+unsafe impl<'a> Send for RayTraceThreadConfig<'a>{}
+unsafe impl<'a> Sync for RayTraceThreadConfig<'a>{}
+
+pub struct RayTracePixelConfig<'a> {
+    scene: &'a Scene,
+    width: usize,
+    height: usize,
+    x: u32,
+    y: u32
+}
+unsafe impl<'a> Send for RayTracePixelConfig<'a>{}
+unsafe impl<'a> Sync for RayTracePixelConfig<'a>{}
+
+//renders one frame into buffer, row major with the top row first
+pub fn render_frame(scene: &Scene, camera: &Camera, settings: &RenderSettings, buffer: &mut [Vec3]) {
+    let mut chunks = buffer.chunks_mut(settings.width);
+    let mut ray_trace_pixel_configs = vec![];
+
+    for y in (0..settings.height).rev() {
+        let mut row = vec![];
+        for x in 0..settings.width {
+            let rtpc = RayTracePixelConfig {
+                scene,
+                width: settings.width,
+                height: settings.height,
+                x: x as u32,
+                y: y as u32
+            };
+            row.push(rtpc);
+        }
+
+        ray_trace_pixel_configs.push(
+            RayTraceThreadConfig{
+                rtpc: row,
+                camera,
+                number_of_samples: settings.samples,
+                pixel_subset: chunks.next().unwrap()
+            }
+        );
+    }
+    let _ : Vec<_> = ray_trace_pixel_configs.par_iter_mut().map(|rtpc|render_thread(rtpc)).collect();
+}
+
+pub fn render_thread(thread_config: &mut RayTraceThreadConfig) {
+    let camera = thread_config.camera;
+    let mut rng = thread_rng();
+
+    for (count, rtpc) in thread_config.rtpc.iter().enumerate() {
+        let mut return_color : Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+        for _ in 0..thread_config.number_of_samples {
+            let x = rtpc.x as f32;
+            let y = rtpc.y as f32;
+ 
+            let u = (x + rng.gen_range(0.0, 1.0)) / rtpc.width as f32;
+            let v = (y + rng.gen_range(0.0, 1.0)) / rtpc.height  as f32;
+            return_color += radiance(camera.get_ray(u, v), rtpc.scene);
+        }
+
+        thread_config.pixel_subset[count] = return_color / thread_config.number_of_samples as f32;
+    }
+}
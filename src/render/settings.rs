@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: mars [--scene <path>] [--width <pixels>] [--height <pixels>] [--samples <n>] [--frames <n>] [--output <path>]
+
+    --scene <path>      scene file to render, the built in demo scene is used without one
+    --width <pixels>    width of the render, defaults to 480
+    --height <pixels>   height of the render, defaults to 320
+    --samples <n>       samples per pixel for each frame, defaults to 4
+    --frames <n>        frames averaged together in a headless render, defaults to 1
+    --output <path>     render headless and write the image to <path> (.png, .jpg, .bmp, .hdr or .exr)
+
+Settings given on the command line override the ones in the scene file.";
+
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples: u32,
+    pub frames: u32,
+    pub output: Option<PathBuf>
+}
+
+impl RenderSettings {
+    pub fn new() -> RenderSettings {
+        RenderSettings {
+            width: 480,
+            height: 320,
+            samples: 4,
+            frames: 1,
+            output: None
+        }
+    }
+
+    //Shared by the command line and the render line of scene files, so both accept the same names
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "width" => self.width = parse_value(key, value)?,
+            "height" => self.height = parse_value(key, value)?,
+            "samples" => self.samples = parse_value(key, value)?,
+            "frames" => self.frames = parse_value(key, value)?,
+            "output" => self.output = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown setting {}", key))
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(String::from("width and height must be greater than zero"));
+        }
+
+        if self.samples == 0 {
+            return Err(String::from("samples must be greater than zero"));
+        }
+
+        if self.frames == 0 {
+            return Err(String::from("frames must be greater than zero"));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings::new()
+    }
+}
+
+pub struct CommandLine {
+    pub scene: Option<PathBuf>,
+    overrides: Vec<(String, String)>
+}
+
+impl CommandLine {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<CommandLine, String> {
+        let mut command_line = CommandLine {
+            scene: None,
+            overrides: vec![]
+        };
+
+        while let Some(arg) = args.next() {
+            let key = match arg.as_str() {
+                "--scene" | "--width" | "--height" | "--samples" | "--frames" | "--output" => &arg[2..],
+                _ => return Err(format!("unknown argument {}", arg))
+            };
+
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err(format!("{} expects a value", arg))
+            };
+
+            if key == "scene" {
+                command_line.scene = Some(PathBuf::from(value));
+            }
+            else {
+                command_line.overrides.push((key.to_string(), value));
+            }
+        }
+
+        Ok(command_line)
+    }
+
+    //Applied after the scene file so the command line wins
+    pub fn apply(&self, settings: &mut RenderSettings) -> Result<(), String> {
+        for (key, value) in &self.overrides {
+            settings.set(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("{} does not accept {}", key, value))
+}
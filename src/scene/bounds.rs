@@ -0,0 +1,62 @@
+use crate::scene::HitableID;
+use crate::glam::Vec3;
+use crate::math::Ray;
+
+use crate::bvh::aabb::{AABB, Bounded};
+use crate::bvh::bounding_hierarchy::BHShape;
+use crate::bvh::nalgebra::{Point3, Vector3};
+use crate::bvh::ray::Ray as BVHRay;
+
+#[inline]
+pub fn to_point(v: Vec3) -> Point3<f32> {
+    Point3::new(v.x(), v.y(), v.z())
+}
+
+#[inline]
+pub fn from_point(p: &Point3<f32>) -> Vec3 {
+    Vec3::new(p.x, p.y, p.z)
+}
+
+#[inline]
+pub fn aabb_from_corners(min: Vec3, max: Vec3) -> AABB {
+    AABB::with_bounds(to_point(min), to_point(max))
+}
+
+#[inline]
+pub fn to_bvh_ray(ray: &Ray) -> BVHRay {
+    let d = ray.direction;
+    BVHRay::new(to_point(ray.origin), Vector3::new(d.x(), d.y(), d.z()))
+}
+
+//What the bvh crate builds over, the hitable itself stays in the HitableLibrary
+pub struct HitableBounds {
+    pub id: HitableID,
+    aabb: AABB,
+    node_index: usize
+}
+
+impl HitableBounds {
+    pub fn new(id: HitableID, aabb: AABB) -> HitableBounds {
+        HitableBounds {
+            id,
+            aabb,
+            node_index: 0
+        }
+    }
+}
+
+impl Bounded for HitableBounds {
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl BHShape for HitableBounds {
+    fn set_bh_node_index(&mut self, index: usize) {
+        self.node_index = index;
+    }
+
+    fn bh_node_index(&self) -> usize {
+        self.node_index
+    }
+}
@@ -0,0 +1,163 @@
+use crate::math::{Ray, luminance};
+use crate::glam::Vec3;
+use crate::scene::{Material, HitRecord, Hitable, ScatterHit, Texture};
+
+use std::sync::Arc;
+
+//Step in uv used to difference height textures, the position moves along with it by the matching distance over the surface
+const BUMP_EPSILON: f32 = 1e-3;
+
+pub enum SurfaceDetail {
+    //Tangent space normals stored as colors, 0.5,0.5,1 is flat. strength 0 flattens it, 1 uses it as is
+    NormalMap { texture: Arc<dyn Texture>, strength: f32 },
+    //Grayscale heights, the normal tilts away from the slope and scale is how far a height of 1 stands out in world units
+    Bump { height: Arc<dyn Texture>, scale: f32 }
+}
+
+impl SurfaceDetail {
+    pub fn apply(&self, record: &mut HitRecord) {
+        let (tangent, bitangent, normal) = (record.tangent, record.bitangent, record.normal);
+        let perturbed = match self {
+            SurfaceDetail::NormalMap { texture, strength } => {
+                let color = texture.value(record.u, record.v, record.position);
+                let local = color * 2.0 - Vec3::one();
+                tangent * (local.x() * strength) + bitangent * (local.y() * strength) + normal * local.z()
+            },
+            SurfaceDetail::Bump { height, scale } => {
+                //Uv and solid textures both see the same step, the slopes are then turned from per uv into per world unit
+                let (du, dv) = (record.du_length, record.dv_length);
+                let h = luminance(height.value(record.u, record.v, record.position));
+                let hu = luminance(height.value(record.u + BUMP_EPSILON, record.v, record.position + tangent * (du * BUMP_EPSILON)));
+                let hv = luminance(height.value(record.u, record.v + BUMP_EPSILON, record.position + bitangent * (dv * BUMP_EPSILON)));
+                let dhdu = (hu - h) / (BUMP_EPSILON * du);
+                let dhdv = (hv - h) / (BUMP_EPSILON * dv);
+                normal - (tangent * dhdu + bitangent * dhdv) * *scale
+            }
+        };
+
+        //A map can not tip the normal flat or under the surface
+        if perturbed.length() < 1e-6 || perturbed.dot(normal) <= 0.0 {
+            return;
+        }
+        record.normal = perturbed.normalize();
+        //Tilting the frame does not stretch the uv layout, so the lengths carry over as they were
+        let (du, dv) = (record.du_length, record.dv_length);
+        record.set_tangent_frame(tangent, bitangent);
+        record.du_length = du;
+        record.dv_length = dv;
+    }
+}
+
+//Wraps any material to give it a normal or bump map, everything else is passed straight through
+pub struct BumpMapped {
+    pub material: Box<dyn Material + Send>,
+    pub detail: SurfaceDetail
+}
+
+impl BumpMapped {
+    pub fn new(material: Box<dyn Material + Send>, detail: SurfaceDetail) -> BumpMapped {
+        BumpMapped {
+            material,
+            detail
+        }
+    }
+}
+
+impl Material for BumpMapped {
+    fn perturb_normal(&self, record: &mut HitRecord) {
+        self.material.perturb_normal(record);
+        self.detail.apply(record);
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &dyn Hitable) -> ScatterHit {
+        self.material.scatter(ray_in, record, hitable)
+    }
+
+    fn eval(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.material.eval(record, wo, wi)
+    }
+
+    fn pdf(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.material.pdf(record, wo, wi)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Vec3 {
+        self.material.emitted(record)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn is_medium(&self) -> bool {
+        self.material.is_medium()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ConstantTexture;
+
+    //Height growing along u, or along x in world space, at the given rate
+    struct UvRamp(f32);
+    struct SolidRamp(f32);
+
+    impl Texture for UvRamp {
+        fn value(&self, u: f32, _v: f32, _position: Vec3) -> Vec3 {
+            Vec3::one() * (u * self.0)
+        }
+    }
+
+    impl Texture for SolidRamp {
+        fn value(&self, _u: f32, _v: f32, position: Vec3) -> Vec3 {
+            Vec3::one() * (position.x() * self.0)
+        }
+    }
+
+    //A point facing up on a surface where one unit of u covers u_length along x and one unit of v covers 2 along z
+    fn record(u_length: f32) -> HitRecord {
+        let mut record = HitRecord::empty();
+        record.position = Vec3::new(3.0, 0.0, -2.0);
+        record.normal = Vec3::new(0.0, 1.0, 0.0);
+        record.u = 0.3;
+        record.v = 0.6;
+        record.set_tangent_frame(Vec3::new(u_length, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+        record
+    }
+
+    fn bumped(height: Arc<dyn Texture>, scale: f32, u_length: f32) -> HitRecord {
+        let mut record = record(u_length);
+        SurfaceDetail::Bump { height, scale }.apply(&mut record);
+        record
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-3, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn constant_height_keeps_the_normal() {
+        let record = bumped(Arc::new(ConstantTexture::new(Vec3::one() * 0.7)), 1.0, 4.0);
+        assert_close(record.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_close(record.tangent, Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn ramp_tilts_the_normal_away_from_the_slope() {
+        //Rising 1 per world unit along x with scale 0.5 leans the normal back toward -x by half its height
+        let expected = Vec3::new(-0.5, 1.0, 0.0).normalize();
+        assert_close(bumped(Arc::new(SolidRamp(1.0)), 0.5, 4.0).normal, expected);
+        //The same slope given per unit of u over a surface 4 units long
+        assert_close(bumped(Arc::new(UvRamp(4.0)), 0.5, 4.0).normal, expected);
+        //Stretching the surface flattens a uv ramp but leaves a solid one alone
+        assert_close(bumped(Arc::new(UvRamp(4.0)), 0.5, 8.0).normal, Vec3::new(-0.25, 1.0, 0.0).normalize());
+        assert_close(bumped(Arc::new(SolidRamp(1.0)), 0.5, 8.0).normal, expected);
+    }
+
+    #[test]
+    fn bump_keeps_the_surface_scale() {
+        let record = bumped(Arc::new(SolidRamp(1.0)), 0.5, 4.0);
+        assert!((record.du_length - 4.0).abs() < 1e-3 && (record.dv_length - 2.0).abs() < 1e-3);
+    }
+}
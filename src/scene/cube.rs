@@ -1,58 +1,156 @@
-use crate::scene::{Hitable, MaterialID, HitRecord, HitableID};
+use crate::scene::{Hitable, MaterialID, HitRecord, HitableID, aabb_from_corners};
 use crate::math::Ray;
-use crate::glam::Vec3;
+use crate::glam::{Vec3, Quat};
+use crate::bvh::aabb::AABB;
 
+use std::f32;
+
+//Axis aligned box between min and max, optionally rotated about its own center and then moved by translation
 pub struct Cube  {
-    center:Vec3,
-    radius: f32,
+    min:Vec3,
+    max:Vec3,
+    rotation: Quat,
+    translation: Vec3,
+    transformed: bool,
     material_id: MaterialID,
-    radius_sqrd: f32,
     id: HitableID
 }
 
+#[inline]
+fn axis(v: Vec3, index: usize) -> f32 {
+    match index {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z()
+    }
+}
+
+#[inline]
+fn unit(index: usize) -> Vec3 {
+    match index {
+        0 => Vec3::new(1.0, 0.0, 0.0),
+        1 => Vec3::new(0.0, 1.0, 0.0),
+        _ => Vec3::new(0.0, 0.0, 1.0)
+    }
+}
+
 impl Cube {
-    pub fn new(center:Vec3, radius: f32, material_id: MaterialID) -> Cube {
+    pub fn new(min:Vec3, max:Vec3, material_id: MaterialID) -> Cube {
         Cube {
-            center,
-            radius,
+            min: min.min(max),
+            max: min.max(max),
+            rotation: Quat::identity(),
+            translation: Vec3::zero(),
+            transformed: false,
             material_id,
-            radius_sqrd: radius * radius,
             id: 0
         }
     }
+
+    pub fn with_transform(mut self, rotation: Quat, translation: Vec3) -> Cube {
+        self.rotation = rotation.normalize();
+        self.translation = translation;
+        self.transformed = true;
+        self
+    }
+
+    #[inline]
+    fn local_center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    //Moves a world space ray into the box's own axis aligned frame, t is unchanged since the transform is rigid
+    #[inline]
+    fn to_local(&self, ray: &Ray) -> Ray {
+        if !self.transformed {
+            return Ray::new_at_time(ray.origin, ray.direction, ray.time);
+        }
+        let center = self.local_center();
+        let inverse = self.rotation.conjugate();
+        Ray::new_at_time(
+            inverse.mul_vec3(ray.origin - self.translation - center) + center,
+            inverse.mul_vec3(ray.direction),
+            ray.time
+        )
+    }
+
+    //Returns the entry and exit distances along the ray and the axis of each, None on a miss
+    #[inline]
+    fn slabs(&self, ray: &Ray) -> Option<(f32, usize, f32, usize)> {
+        let mut t_near = f32::MIN;
+        let mut t_far = f32::MAX;
+        let mut near_axis = 0;
+        let mut far_axis = 0;
+
+        for i in 0..3 {
+            let inverse = 1.0 / axis(ray.direction, i);
+            let mut t0 = (axis(self.min, i) - axis(ray.origin, i)) * inverse;
+            let mut t1 = (axis(self.max, i) - axis(ray.origin, i)) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_near {
+                t_near = t0;
+                near_axis = i;
+            }
+            if t1 < t_far {
+                t_far = t1;
+                far_axis = i;
+            }
+            if t_far < t_near {
+                return None;
+            }
+        }
+
+        Some((t_near, near_axis, t_far, far_axis))
+    }
 }
 
 impl Hitable for Cube {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
-        
-        let oc = ray.get_origin() - self.center;
-        let a = ray.direction.dot(ray.direction);
-        let b = oc.dot(ray.direction);
-        let c = oc.dot(oc) - self.radius_sqrd;
-        let d = b * b - a * c;
-        if d > 0.0 {
-            let d = d.sqrt();
-            let temp = (-b - d) / a;
-            if temp < t_max && temp > t_min {
-                record.material = self.material_id;
-                record.t = temp;
-                record.position = ray.point_at_paramater(record.t);
-                record.normal = (record.position - self.center) / self.radius;
-                record.hitable = self.id;
-                return true;
-            }
+        let local = self.to_local(ray);
+        let (t_near, near_axis, t_far, far_axis) = match self.slabs(&local) {
+            Some(slabs) => slabs,
+            None => return false
+        };
 
-            let temp = (-b + d) / a;
-            if temp < t_max && temp > t_min {
-                record.material = self.material_id;
-                record.t = temp;
-                record.position = ray.point_at_paramater(record.t);
-                record.normal = (record.position - self.center) / self.radius;
-                record.hitable = self.id;
-                return true;
-            }
+        let (t, hit_axis) = if t_near < t_max && t_near > t_min {
+            (t_near, near_axis)
+        }
+        else if t_far < t_max && t_far > t_min {
+            (t_far, far_axis)
+        }
+        else {
+            return false;
+        };
+
+        let p = local.point_at_paramater(t);
+        let size = self.max - self.min;
+        let center = self.local_center();
+        let side = if axis(p, hit_axis) > axis(center, hit_axis) { 1.0 } else { -1.0 };
+
+        //u runs along the next axis over and v along the one after, each face is mapped onto the whole 0 to 1 square
+        let u_axis = (hit_axis + 1) % 3;
+        let v_axis = (hit_axis + 2) % 3;
+        let normal = unit(hit_axis) * side;
+
+        record.material = self.material_id;
+        record.t = t;
+        record.position = ray.point_at_paramater(t);
+        record.normal = self.rotation.mul_vec3(normal);
+        record.hitable = self.id;
+        record.u = (axis(p, u_axis) - axis(self.min, u_axis)) / axis(size, u_axis);
+        record.v = (axis(p, v_axis) - axis(self.min, v_axis)) / axis(size, v_axis);
+        record.set_tangent_frame(self.rotation.mul_vec3(unit(u_axis) * axis(size, u_axis)), self.rotation.mul_vec3(unit(v_axis) * axis(size, v_axis)));
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        match self.slabs(&self.to_local(ray)) {
+            Some((_, _, t_far, _)) => t_far > 0.0,
+            None => false
         }
-        return false;
     }
 
     fn set_hitable_id(&mut self, id: HitableID) {
@@ -60,11 +158,110 @@ impl Hitable for Cube {
     }
 
     fn get_center(&self) -> Vec3 {
-        return self.center;
+        self.local_center() + self.translation
     }
 
     fn get_radius(&self) -> f32 {
-        return self.radius;
+        (self.max - self.min).length() * 0.5
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let center = self.local_center();
+        let mut min = Vec3::one() * f32::MAX;
+        let mut max = Vec3::one() * f32::MIN;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.min.x() } else { self.max.x() },
+                if i & 2 == 0 { self.min.y() } else { self.max.y() },
+                if i & 4 == 0 { self.min.z() } else { self.max.z() }
+            );
+            let corner = self.rotation.mul_vec3(corner - center) + center + self.translation;
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+        Some(aabb_from_corners(min, max))
     }
 
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glam::deg;
+    use crate::rand::{Rng, SeedableRng};
+    use crate::rand::rngs::StdRng;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-3, "{:?} is not {:?}", a, b);
+    }
+
+    fn hit(cube: &Cube, ray: &Ray) -> Option<HitRecord> {
+        let mut record = HitRecord::empty();
+        if cube.hit(ray, 0.001, f32::MAX, &mut record) { Some(record) } else { None }
+    }
+
+    #[test]
+    fn hits_each_face_from_outside() {
+        let (min, max) = (Vec3::new(-1.0, 0.0, 1.0), Vec3::new(3.0, 1.0, 4.0));
+        let cube = Cube::new(min, max, 0);
+        let center = (min + max) * 0.5;
+        let half = (max - min) * 0.5;
+
+        for i in 0..3 {
+            for side in &[1.0, -1.0] {
+                let outward = unit(i) * *side;
+                //Off center so the ray does not run along an edge between faces
+                let origin = center + outward * 10.0 + Vec3::new(0.1, 0.1, 0.1) - unit(i) * 0.1;
+                let record = hit(&cube, &Ray::new(origin, -outward)).unwrap();
+                assert!((record.t - (10.0 - axis(half, i))).abs() < 1e-4, "face {} {}: t is {}", i, side, record.t);
+                assert_close(record.normal, outward);
+                assert_close(record.position, origin - outward * record.t);
+            }
+        }
+    }
+
+    #[test]
+    fn hits_from_inside_on_the_far_face() {
+        let cube = Cube::new(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(1.0, 2.0, 3.0), 0);
+        let record = hit(&cube, &Ray::new(Vec3::new(0.1, 0.2, 0.3), Vec3::new(0.0, 0.0, -1.0))).unwrap();
+        assert!((record.t - 3.3).abs() < 1e-4, "t is {}", record.t);
+        //Normals point out of the box even when the ray starts inside it
+        assert_close(record.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(cube.quick_hit(&Ray::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0))));
+    }
+
+    #[test]
+    fn rotated_cube_matches_the_ray_in_its_own_frame() {
+        let (min, max) = (Vec3::new(-1.0, -0.5, -2.0), Vec3::new(1.0, 0.5, 0.0));
+        let rotation = Quat::from_rotation_y(deg(40.0)).mul_quat(Quat::from_rotation_x(deg(-25.0)));
+        let translation = Vec3::new(2.0, -1.0, 0.5);
+        let rotated = Cube::new(min, max, 0).with_transform(rotation, translation);
+        let aligned = Cube::new(min, max, 0);
+        let center = (min + max) * 0.5;
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Vec3::new(rng.gen_range(-6.0, 6.0), rng.gen_range(-6.0, 6.0), rng.gen_range(-6.0, 6.0));
+            let target = translation + center + Vec3::new(rng.gen_range(-1.5, 1.5), rng.gen_range(-1.5, 1.5), rng.gen_range(-1.5, 1.5));
+            let ray = Ray::new(origin, target - origin);
+            let local = Ray::new(rotation.conjugate().mul_vec3(origin - translation - center) + center, rotation.conjugate().mul_vec3(ray.direction));
+
+            match (hit(&rotated, &ray), hit(&aligned, &local)) {
+                (Some(world), Some(expected)) => {
+                    hits += 1;
+                    assert!((world.t - expected.t).abs() < 1e-3, "t is {} not {}", world.t, expected.t);
+                    assert_close(world.normal, rotation.mul_vec3(expected.normal));
+                    assert_close(world.position, rotation.mul_vec3(expected.position - center) + center + translation);
+                    assert!((world.u - expected.u).abs() < 1e-3 && (world.v - expected.v).abs() < 1e-3);
+                },
+                (None, None) => {},
+                _ => panic!("the rotated and aligned cubes disagree on hitting {:?}", ray.direction)
+            }
+        }
+        assert!(hits > 500, "only {} rays hit", hits);
+    }
 }
@@ -0,0 +1,299 @@
+use crate::glam::Vec3;
+use crate::math::luminance;
+use crate::scene::Daylight;
+use crate::image;
+use crate::image::hdr::HDRDecoder;
+use crate::exr::prelude::read_first_rgba_layer_from_file;
+use crate::rand::{thread_rng, Rng};
+
+use std::f32;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+//What a ray sees when it leaves the scene without hitting anything, and the light that comes in from there
+pub enum Environment {
+    //Black turns the sky off so only emissive materials and lights light the scene
+    Constant(Vec3),
+    //Blends from bottom looking straight down to top looking straight up
+    Gradient { bottom: Vec3, top: Vec3 },
+    //An image wrapped around the whole scene
+    Map(EnvironmentMap),
+    //Analytic clear sky with a sun
+    Daylight(Daylight)
+}
+
+impl Environment {
+    //The white to blue gradient the demo scene has always used
+    pub fn sky() -> Environment {
+        Environment::Gradient {
+            bottom: Vec3::one(),
+            top: Vec3::new(0.5, 0.7, 1.0)
+        }
+    }
+
+    //direction is expected to be normalized
+    #[inline]
+    pub fn color(&self, direction: Vec3) -> Vec3 {
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.y() + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            },
+            Environment::Map(map) => map.color(direction),
+            Environment::Daylight(daylight) => daylight.color(direction)
+        }
+    }
+
+    //Solid angle density of sample returning direction, 0 when the environment is only found by BSDF sampling
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        match self {
+            Environment::Map(map) => map.pdf(direction),
+            Environment::Daylight(daylight) => daylight.pdf(direction),
+            _ => 0.0
+        }
+    }
+
+    //A direction toward the environment and its pdf, smooth environments are left to BSDF sampling
+    pub fn sample(&self) -> Option<(Vec3, f32)> {
+        match self {
+            Environment::Map(map) => map.sample(),
+            Environment::Daylight(daylight) => daylight.sample(),
+            _ => None
+        }
+    }
+}
+
+//Equirectangular image, the middle of the image is straight ahead down -z and the top row straight up.
+//Directions are picked in proportion to pixel luminance so small bright features like the sun are found by shadow rays.
+pub struct EnvironmentMap {
+    pixels: Vec<Vec3>,
+    width: usize,
+    height: usize,
+    //Radians the map is turned about the y axis
+    rotation: f32,
+    //Cumulative distributions from 0 to 1, over the rows and then over the pixels within each row
+    rows: Vec<f32>,
+    columns: Vec<f32>,
+    //Sum of every pixel's sampling weight, 0 for a black map which is then never sampled
+    total: f32
+}
+
+impl EnvironmentMap {
+    pub fn new(pixels: Vec<Vec3>, width: usize, height: usize, rotation: f32) -> EnvironmentMap {
+        let mut rows = Vec::with_capacity(height + 1);
+        let mut columns = Vec::with_capacity(height * (width + 1));
+        let mut total = 0.0;
+        rows.push(0.0);
+
+        for y in 0..height {
+            //Rows near the poles cover less of the sphere
+            let sin_theta = ((y as f32 + 0.5) / height as f32 * PI).sin();
+            let start = columns.len();
+            let mut row_total = 0.0;
+            columns.push(0.0);
+            for x in 0..width {
+                row_total += luminance(pixels[y * width + x]).max(0.0) * sin_theta;
+                columns.push(row_total);
+            }
+            for (x, value) in columns[start..].iter_mut().enumerate() {
+                *value = if row_total > 0.0 { *value / row_total } else { x as f32 / width as f32 };
+            }
+            total += row_total;
+            rows.push(total);
+        }
+        for (y, value) in rows.iter_mut().enumerate() {
+            *value = if total > 0.0 { *value / total } else { y as f32 / height as f32 };
+        }
+
+        EnvironmentMap {
+            pixels,
+            width,
+            height,
+            rotation,
+            rows,
+            columns,
+            total
+        }
+    }
+
+    //Reads .hdr and .exr as linear radiance, anything else the image crate opens is used as stored
+    pub fn load(path: &Path, intensity: f32, rotation: f32) -> Result<EnvironmentMap, String> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        let (pixels, width, height) = match extension.as_str() {
+            "hdr" => read_hdr(path),
+            "exr" => read_exr(path),
+            _ => {
+                let image = image::open(path).map_err(|e| e.to_string())?.to_rgb();
+                let pixels = image.pixels().map(|p| Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32) / 255.0).collect();
+                Ok((pixels, image.width() as usize, image.height() as usize))
+            }
+        }.map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if width == 0 || height == 0 {
+            return Err(format!("{}: image is empty", path.display()));
+        }
+        //Infinities and NaNs would poison the sampling distribution
+        let pixels = pixels.into_iter().map(|p| if p.x().is_finite() && p.y().is_finite() && p.z().is_finite() { p * intensity } else { Vec3::zero() }).collect();
+        Ok(EnvironmentMap::new(pixels, width, height, rotation))
+    }
+
+    #[inline]
+    fn direction_to_uv(&self, direction: Vec3) -> (f32, f32) {
+        let (sin, cos) = (-self.rotation).sin_cos();
+        let x = direction.x() * cos + direction.z() * sin;
+        let z = direction.z() * cos - direction.x() * sin;
+        let u = 0.5 + x.atan2(-z) / (2.0 * PI);
+        let v = direction.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    #[inline]
+    fn uv_to_direction(&self, u: f32, v: f32) -> Vec3 {
+        let (sin_theta, cos_theta) = (v * PI).sin_cos();
+        let (sin_phi, cos_phi) = ((u - 0.5) * 2.0 * PI).sin_cos();
+        let (x, z) = (sin_theta * sin_phi, -sin_theta * cos_phi);
+        let (sin, cos) = self.rotation.sin_cos();
+        Vec3::new(x * cos + z * sin, cos_theta, z * cos - x * sin)
+    }
+
+    #[inline]
+    fn pixel(&self, u: f32, v: f32) -> usize {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        y * self.width + x
+    }
+
+    pub fn color(&self, direction: Vec3) -> Vec3 {
+        let (u, v) = self.direction_to_uv(direction);
+        self.pixels[self.pixel(u, v)]
+    }
+
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let index = self.pixel(u, v);
+        let (x, y) = (index % self.width, index / self.width);
+        let row = self.rows[y + 1] - self.rows[y];
+        let start = y * (self.width + 1);
+        let column = self.columns[start + x + 1] - self.columns[start + x];
+        //Density over the image, then stretched over the sphere
+        let pdf_uv = row * column * (self.width * self.height) as f32;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn sample(&self) -> Option<(Vec3, f32)> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        let mut rng = thread_rng();
+        let (y, dv) = sample_distribution(&self.rows, rng.gen::<f32>());
+        let start = y * (self.width + 1);
+        let (x, du) = sample_distribution(&self.columns[start..start + self.width + 1], rng.gen::<f32>());
+
+        let u = (x as f32 + du) / self.width as f32;
+        let v = (y as f32 + dv) / self.height as f32;
+        let direction = self.uv_to_direction(u, v);
+        let pdf = self.pdf(direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((direction, pdf))
+    }
+}
+
+//Picks the bucket of a cumulative distribution that sample falls into and how far across it the sample landed
+#[inline]
+fn sample_distribution(cdf: &[f32], sample: f32) -> (usize, f32) {
+    let index = cdf.partition_point(|value| *value <= sample).clamp(1, cdf.len() - 1) - 1;
+    let width = cdf[index + 1] - cdf[index];
+    let offset = if width > 0.0 { ((sample - cdf[index]) / width).clamp(0.0, 1.0) } else { 0.5 };
+    (index, offset)
+}
+
+fn read_hdr(path: &Path) -> Result<(Vec<Vec3>, usize, usize), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = HDRDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr().map_err(|e| e.to_string())?.iter().map(|p| Vec3::new(p[0], p[1], p[2])).collect();
+    Ok((pixels, metadata.width as usize, metadata.height as usize))
+}
+
+//Any OpenEXR the exr crate can decode, whatever its compression, as long as the first layer has RGB channels
+fn read_exr(path: &Path) -> Result<(Vec<Vec3>, usize, usize), String> {
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| (vec![Vec3::zero(); resolution.width() * resolution.height()], resolution.width()),
+        |(pixels, width), position, (r, g, b, _): (f32, f32, f32, f32)| pixels[position.y() * *width + position.x()] = Vec3::new(r, g, b)
+    ).map_err(|e| e.to_string())?;
+
+    let (pixels, width) = image.layer_data.channel_data.pixels;
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    Ok((pixels, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::write_image;
+    use crate::exr::prelude::{Encoding, Image, SpecificChannels, Vec2, WritableImage};
+
+    use crate::scene::temp_dir::TempDir;
+
+    const WIDTH: usize = 7;
+    const HEIGHT: usize = 4;
+
+    //A ramp with values above 1 and a different value in every channel so flipped rows or swapped channels show up
+    fn radiance() -> Vec<Vec3> {
+        (0..WIDTH * HEIGHT).map(|i| Vec3::new(i as f32 * 0.25, 1.0 + (i % WIDTH) as f32, 0.5 + (i / WIDTH) as f32 * 3.0)).collect()
+    }
+
+    fn assert_loads(path: &Path, pixels: &[Vec3], tolerance: f32) {
+        let map = EnvironmentMap::load(path, 1.0, 0.0).unwrap();
+        assert_eq!((map.width, map.height), (WIDTH, HEIGHT));
+        for (i, (loaded, written)) in map.pixels.iter().zip(pixels).enumerate() {
+            assert!((*loaded - *written).length() <= tolerance * written.length(), "pixel {} loaded as {:?}, written as {:?}", i, loaded, written);
+        }
+    }
+
+    #[test]
+    fn reads_back_written_exr() {
+        let pixels = radiance();
+        let dir = TempDir::new("environment_reads_back_written_exr");
+        let path = dir.join("written.exr");
+        write_image(&path, &pixels, WIDTH, HEIGHT).unwrap();
+        assert_loads(&path, &pixels, 0.0);
+    }
+
+    #[test]
+    fn reads_back_written_hdr() {
+        let pixels = radiance();
+        let dir = TempDir::new("environment_reads_back_written_hdr");
+        let path = dir.join("written.hdr");
+        write_image(&path, &pixels, WIDTH, HEIGHT).unwrap();
+        //RGBE shares one exponent between the channels, so the smaller ones lose a few bits
+        assert_loads(&path, &pixels, 0.01);
+    }
+
+    #[test]
+    fn reads_compressed_exr() {
+        let pixels = radiance();
+        let dir = TempDir::new("environment_reads_compressed_exr");
+        let path = dir.join("compressed.exr");
+        let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+            let p = pixels[y * WIDTH + x];
+            (p.x(), p.y(), p.z())
+        });
+        Image::from_encoded_channels((WIDTH, HEIGHT), Encoding::SMALL_LOSSLESS, channels).write().to_file(&path).unwrap();
+        assert_loads(&path, &pixels, 0.0);
+    }
+}
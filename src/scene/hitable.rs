@@ -1,35 +1,26 @@
-use crate::math::{Ray};
+use crate::math::{Ray, orthonormal_basis};
 use crate::scene::{MaterialID};
 use crate::glam::Vec3;
+use crate::bvh::aabb::AABB;
+use crate::scene::aabb_from_corners;
 
 pub type HitableID = u32;
 
-#[derive(Clone, Copy)]
-pub struct RayCastResult {
-    pub hits: [HitRecord; 10],
-    pub number_of_hits: usize
-}
-
-impl RayCastResult {
-    pub fn new() -> RayCastResult {
-        RayCastResult {
-            hits: [HitRecord::empty(); 10],
-            number_of_hits: 0
-        }
-    }
-
-    pub fn reset(&mut self) {
-        self.number_of_hits = 0;
-    }
-}
-
 #[derive(Clone, Copy)]
 pub struct HitRecord {
     pub t: f32,
     pub position:Vec3,
     pub normal:Vec3,
     pub material: MaterialID,
-    pub hitable: HitableID
+    pub hitable: HitableID,
+    pub u: f32,
+    pub v: f32,
+    //Unit vectors along the surface in the directions u and v grow, perpendicular to normal
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    //World distance the surface moves along tangent and bitangent for one unit of u and of v
+    pub du_length: f32,
+    pub dv_length: f32
 }
 
 impl HitRecord {
@@ -39,7 +30,13 @@ impl HitRecord {
             position:Vec3::new(0.0, 0.0, 0.0),
             normal:Vec3::new(0.0, 0.0, 0.0),
             material: 0,
-            hitable: 0
+            hitable: 0,
+            u: 0.0,
+            v: 0.0,
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 0.0, 1.0),
+            du_length: 1.0,
+            dv_length: 1.0
         }
     }
 
@@ -50,13 +47,82 @@ impl HitRecord {
         self.normal = other.normal;
         self.material = other.material;
         self.hitable = other.hitable;
+        self.u = other.u;
+        self.v = other.v;
+        self.tangent = other.tangent;
+        self.bitangent = other.bitangent;
+        self.du_length = other.du_length;
+        self.dv_length = other.dv_length;
+    }
+
+    //Takes the surface derivatives along u and v, call after normal is set.
+    //The tangent is made perpendicular to the normal and the bitangent keeps the side dpdv was on,
+    //so mirrored uv layouts still come out the right way round. Degenerate derivatives fall back to any frame.
+    #[inline]
+    pub fn set_tangent_frame(&mut self, dpdu: Vec3, dpdv: Vec3) {
+        let tangent = dpdu - self.normal * self.normal.dot(dpdu);
+        if tangent.length() < 1e-8 {
+            let (tangent, bitangent) = orthonormal_basis(self.normal);
+            self.tangent = tangent;
+            self.bitangent = bitangent;
+            self.du_length = 1.0;
+            self.dv_length = 1.0;
+            return;
+        }
+
+        self.tangent = tangent.normalize();
+        self.bitangent = self.normal.cross(self.tangent);
+        if self.bitangent.dot(dpdv) < 0.0 {
+            self.bitangent = -self.bitangent;
+        }
+        self.du_length = tangent.length();
+        let dv_length = self.bitangent.dot(dpdv);
+        self.dv_length = if dv_length < 1e-8 { self.du_length } else { dv_length };
     }
 }
 
-pub trait Hitable: Send {
+//Sync so one object can be shared by several instances across render threads
+pub trait Hitable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f32, t_mac: f32, record: &mut HitRecord) -> bool;
     fn quick_hit(&self, ray: &Ray) -> bool;
     fn set_hitable_id(&mut self, id: HitableID);
     fn get_center(&self) -> Vec3;
     fn get_radius(&self) -> f32;
+
+    //None for shapes with no finite bounds, those are tested against every ray instead of going in the BVH
+    fn bounding_box(&self) -> Option<AABB> {
+        let extent = Vec3::one() * self.get_radius().abs();
+        Some(aabb_from_corners(self.get_center() - extent, self.get_center() + extent))
+    }
+
+    //The material of the whole shape, None when it has several or only wraps another shape's
+    fn material_id(&self) -> Option<MaterialID> {
+        None
+    }
+
+    //Solid angle density of random picking direction from origin, shapes that can not be sampled leave it at 0
+    //and are then only found by bouncing into them
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3, _time: f32) -> f32 {
+        0.0
+    }
+
+    //A direction from origin toward a random point on the shape, used to aim shadow rays at lights
+    fn random(&self, _origin: Vec3, _time: f32) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+//pdf_value for flat shapes sampled uniformly over their area, converts the area density to solid angle at the hit
+pub fn flat_light_pdf(hitable: &dyn Hitable, area: f32, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+    let mut record = HitRecord::empty();
+    if area <= 0.0 || !hitable.hit(&Ray::new_at_time(origin, direction, time), 0.001, f32::MAX, &mut record) {
+        return 0.0;
+    }
+    let length_squared = direction.dot(direction);
+    let distance_squared = record.t * record.t * length_squared;
+    let cosine = record.normal.dot(direction).abs() / length_squared.sqrt();
+    if cosine < 1e-6 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
 }
\ No newline at end of file
@@ -1,8 +1,9 @@
 use crate::scene::{Hitable, HitRecord, HitableID};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub struct HitableLibrary {
-     library: HashMap<HitableID, Box<Hitable + Send>>,
+     library: HashMap<HitableID, Arc<Hitable + Send>>,
      id_count: HitableID
 }
 
@@ -17,11 +18,16 @@ impl HitableLibrary {
     pub fn add_hitable_to_library(&mut self, mut hitable: Box<Hitable + Send>) -> HitableID {
         self.id_count += 1;
         hitable.set_hitable_id(self.id_count);
-        self.library.insert(self.id_count, hitable);
+        self.library.insert(self.id_count, Arc::from(hitable));
         return self.id_count;
     }
 
-    pub fn checkout_hitable(&self, id: HitableID) -> Option<&Box<Hitable + Send>> {
+    pub fn checkout_hitable(&self, id: HitableID) -> Option<&Arc<Hitable + Send>> {
         return self.library.get(&id);
     }
+
+    //A handle to an object already in the library, for instances that reuse it
+    pub fn share_hitable(&self, id: HitableID) -> Option<Arc<dyn Hitable + Send>> {
+        self.library.get(&id).cloned()
+    }
 }
\ No newline at end of file
@@ -1,36 +1,154 @@
-use crate::scene::{Hitable, HitRecord, HitableID, HitableLibrary};
+use crate::scene::{HitRecord, HitableID, HitableLibrary, HitableBounds, to_bvh_ray};
 use crate::math::Ray;
+use crate::bvh::bvh::BVH;
 
 pub struct HitableList {
-    id_list: Vec<HitableID>
+    id_list: Vec<HitableID>,
+    bounds: Vec<HitableBounds>,
+    unbounded: Vec<HitableID>,
+    bvh: Option<BVH>
 }
 
 impl HitableList {
-    pub fn new_with_hitable_id_list(list: Vec<HitableID>) -> HitableList {
+    pub fn new_with_hitable_id_list(list: Vec<HitableID>, hitable_library: &HitableLibrary) -> HitableList {
+        let mut bounds = vec![];
+        let mut unbounded = vec![];
+
+        for id in &list {
+            match hitable_library.checkout_hitable(*id) {
+                Some(object) => {
+                    match object.bounding_box() {
+                        Some(aabb) => bounds.push(HitableBounds::new(*id, aabb)),
+                        None => unbounded.push(*id)
+                    }
+                },
+                None => {panic!("Object {} does not exist", id);}
+            }
+        }
+
+        let bvh = if bounds.is_empty() {
+            None
+        }
+        else {
+            Some(BVH::build(&mut bounds))
+        };
+
         HitableList {
-            id_list: list
+            id_list: list,
+            bounds,
+            unbounded,
+            bvh
         }
     }
 
+    pub fn ids(&self) -> &[HitableID] {
+        &self.id_list
+    }
+
     pub fn cast_ray_into_world(&self, ray_in: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord, hitable_library: &HitableLibrary) -> bool {
         let mut temp_rec = HitRecord::empty();
 
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
+        if let Some(bvh) = &self.bvh {
+            for bounds in bvh.traverse(&to_bvh_ray(ray_in), &self.bounds) {
+                if self.hit_object(bounds.id, ray_in, t_min, closest_so_far, &mut temp_rec, hitable_library) {
+                    hit_anything = true;
+                    closest_so_far = temp_rec.t;
+                    record.copy_over(&temp_rec);
+                }
+            }
+        }
+
+        for id in &self.unbounded {
+            if self.hit_object(*id, ray_in, t_min, closest_so_far, &mut temp_rec, hitable_library) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                record.copy_over(&temp_rec);
+            }
+        }
+        hit_anything
+    }
+
+    //Tests every object without the BVH, slow but a useful reference for the accelerated path
+    pub fn cast_ray_into_world_linear(&self, ray_in: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord, hitable_library: &HitableLibrary) -> bool {
+        let mut temp_rec = HitRecord::empty();
+
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
         for id in &self.id_list {
-            let object = hitable_library.checkout_hitable(*id);
-            match object {
-                Some(unwraped_object) => {
-                    if unwraped_object.hit(ray_in, t_min, closest_so_far, &mut temp_rec) {
-                        hit_anything = true;
-                        closest_so_far = temp_rec.t;
-                        record.copy_over(&temp_rec);
-                    }
-                },
-                None => {panic!("Object {} does not exist", id);}
+            if self.hit_object(*id, ray_in, t_min, closest_so_far, &mut temp_rec, hitable_library) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                record.copy_over(&temp_rec);
             }
         }
-        return hit_anything;
+        hit_anything
+    }
+
+    #[inline]
+    fn hit_object(&self, id: HitableID, ray_in: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord, hitable_library: &HitableLibrary) -> bool {
+        match hitable_library.checkout_hitable(id) {
+            Some(unwraped_object) => unwraped_object.hit(ray_in, t_min, t_max, record),
+            None => {panic!("Object {} does not exist", id);}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{Sphere, Cube, Plane, Hitable};
+    use crate::glam::Vec3;
+    use crate::rand::{Rng, SeedableRng};
+    use crate::rand::rngs::StdRng;
+
+    fn random_point(rng: &mut StdRng, extent: f32) -> Vec3 {
+        Vec3::new(rng.gen_range(-extent, extent), rng.gen_range(-extent, extent), rng.gen_range(-extent, extent))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn bvh_matches_linear() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut library = HitableLibrary::new();
+        let mut ids = vec![];
+
+        for index in 0..3000 {
+            let center = random_point(&mut rng, 50.0);
+            let object : Box<dyn Hitable + Send> = match index % 2 {
+                0 => Box::new(Sphere::new(center, rng.gen_range(0.1, 2.0), index)),
+                _ => {
+                    let size = Vec3::new(rng.gen_range(0.1, 2.0), rng.gen_range(0.1, 2.0), rng.gen_range(0.1, 2.0));
+                    Box::new(Cube::new(center - size, center + size, index))
+                }
+            };
+            ids.push(library.add_hitable_to_library(object));
+        }
+        for index in 0..4 {
+            let normal = random_point(&mut rng, 1.0);
+            ids.push(library.add_hitable_to_library(Box::new(Plane::new(random_point(&mut rng, 60.0), normal, 3000 + index))));
+        }
+
+        let world = HitableList::new_with_hitable_id_list(ids, &library);
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let ray = Ray::new(random_point(&mut rng, 70.0), random_point(&mut rng, 1.0));
+            let mut bvh_record = HitRecord::empty();
+            let mut linear_record = HitRecord::empty();
+            let bvh_hit = world.cast_ray_into_world(&ray, 0.001, f32::MAX, &mut bvh_record, &library);
+            let linear_hit = world.cast_ray_into_world_linear(&ray, 0.001, f32::MAX, &mut linear_record, &library);
+
+            assert_eq!(bvh_hit, linear_hit);
+            if bvh_hit {
+                hits += 1;
+                assert_eq!(bvh_record.t, linear_record.t);
+                assert_eq!(bvh_record.hitable, linear_record.hitable);
+                assert_eq!(bvh_record.material, linear_record.material);
+            }
+        }
+        //Most rays should find something or the comparison says little
+        assert!(hits > 2500);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::scene::{Hitable, HitRecord, HitableID, MaterialID, aabb_from_corners, from_point};
+use crate::math::Ray;
+use crate::glam::{Vec3, Quat, Mat4};
+use crate::bvh::aabb::AABB;
+
+use std::f32;
+use std::sync::Arc;
+
+//Places a shared object in the world with its own affine transform, so one mesh or shape can appear many times.
+//Rays are moved into the object's space without renormalizing, which keeps t the same in both spaces.
+pub struct Instance {
+    object: Arc<dyn Hitable + Send>,
+    transform: Mat4,
+    inverse: Mat4,
+    normal_transform: Mat4,
+    //Rotation, translation and uniform scale keep angles, so the object's solid angle densities still hold
+    similarity: bool,
+    id: HitableID
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hitable + Send>, transform: Mat4) -> Instance {
+        let inverse = transform.inverse();
+        let x = transform.transform_vector3(Vec3::new(1.0, 0.0, 0.0));
+        let y = transform.transform_vector3(Vec3::new(0.0, 1.0, 0.0));
+        let z = transform.transform_vector3(Vec3::new(0.0, 0.0, 1.0));
+        let tolerance = 1e-4 * x.length();
+        let similarity = (x.length() - y.length()).abs() < tolerance && (x.length() - z.length()).abs() < tolerance
+            && x.dot(y).abs() < tolerance && x.dot(z).abs() < tolerance && y.dot(z).abs() < tolerance;
+
+        Instance {
+            object,
+            transform,
+            inverse,
+            normal_transform: inverse.transpose(),
+            similarity,
+            id: 0
+        }
+    }
+
+    pub fn from_scale_rotation_translation(object: Arc<dyn Hitable + Send>, scale: Vec3, rotation: Quat, translation: Vec3) -> Instance {
+        Instance::new(object, Mat4::from_scale_rotation_translation(scale, rotation.normalize(), translation))
+    }
+
+    #[inline]
+    fn to_object(&self, ray: &Ray) -> Ray {
+        Ray::new_at_time(self.inverse.transform_point3(ray.origin), self.inverse.transform_vector3(ray.direction), ray.time)
+    }
+}
+
+impl Hitable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        if !self.object.hit(&self.to_object(ray), t_min, t_max, record) {
+            return false;
+        }
+
+        record.position = ray.point_at_paramater(record.t);
+        record.normal = self.normal_transform.transform_vector3(record.normal).normalize();
+        record.hitable = self.id;
+        let (tangent, bitangent) = (record.tangent * record.du_length, record.bitangent * record.dv_length);
+        record.set_tangent_frame(self.transform.transform_vector3(tangent), self.transform.transform_vector3(bitangent));
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        self.object.quick_hit(&self.to_object(ray))
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        self.transform.transform_point3(self.object.get_center())
+    }
+
+    //Scaled by the longest axis so the sphere still contains the object
+    fn get_radius(&self) -> f32 {
+        let scale = self.transform.transform_vector3(Vec3::new(1.0, 0.0, 0.0)).length()
+            .max(self.transform.transform_vector3(Vec3::new(0.0, 1.0, 0.0)).length())
+            .max(self.transform.transform_vector3(Vec3::new(0.0, 0.0, 1.0)).length());
+        self.object.get_radius() * scale
+    }
+
+    //Box around the eight transformed corners of the object's box
+    fn bounding_box(&self) -> Option<AABB> {
+        let aabb = self.object.bounding_box()?;
+        let (min, max) = (from_point(&aabb.min), from_point(&aabb.max));
+        let mut world_min = Vec3::one() * f32::MAX;
+        let mut world_max = Vec3::one() * f32::MIN;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() }
+            );
+            let corner = self.transform.transform_point3(corner);
+            world_min = world_min.min(corner);
+            world_max = world_max.max(corner);
+        }
+        Some(aabb_from_corners(world_min, world_max))
+    }
+
+    fn material_id(&self) -> Option<MaterialID> {
+        self.object.material_id()
+    }
+
+    //Other transforms would need the Jacobian of the mapping, those instances are left to be found by bouncing
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        if !self.similarity {
+            return 0.0;
+        }
+        self.object.pdf_value(self.inverse.transform_point3(origin), self.inverse.transform_vector3(direction), time)
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        self.transform.transform_vector3(self.object.random(self.inverse.transform_point3(origin), time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Sphere;
+    use crate::glam::deg;
+    use crate::rand::{Rng, SeedableRng};
+    use crate::rand::rngs::StdRng;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-3, "{:?} is not {:?}", a, b);
+    }
+
+    fn random_point(rng: &mut StdRng, extent: f32) -> Vec3 {
+        Vec3::new(rng.gen_range(-extent, extent), rng.gen_range(-extent, extent), rng.gen_range(-extent, extent))
+    }
+
+    #[test]
+    fn matches_the_object_hit_with_the_inverse_ray() {
+        let sphere : Arc<dyn Hitable + Send> = Arc::new(Sphere::new(Vec3::new(0.5, 0.0, -0.5), 1.0, 0));
+        let scale = Vec3::new(2.0, 1.0, 0.5);
+        let translation = Vec3::new(3.0, -1.0, 2.0);
+        let instance = Instance::from_scale_rotation_translation(sphere.clone(), scale, Quat::identity(), translation);
+        let bounds = instance.bounding_box().unwrap();
+        let (min, max) = (from_point(&bounds.min), from_point(&bounds.max));
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_point(&mut rng, 8.0);
+            let ray = Ray::new(origin, Vec3::new(0.5, 0.0, -0.5) * scale + translation + random_point(&mut rng, 1.0) - origin);
+            let local = Ray::new((origin - translation) / scale, ray.direction / scale);
+
+            let mut world = HitRecord::empty();
+            let mut object = HitRecord::empty();
+            let hit = instance.hit(&ray, 0.001, f32::MAX, &mut world);
+            assert_eq!(hit, sphere.hit(&local, 0.001, f32::MAX, &mut object));
+            if !hit {
+                continue;
+            }
+            hits += 1;
+
+            assert!((world.t - object.t).abs() < 1e-4, "t is {} not {}", world.t, object.t);
+            assert_close(world.position, object.position * scale + translation);
+            //An ellipsoid's normal leans toward its short axes, the sphere normal divided by the scale
+            assert_close(world.normal, (object.normal / scale).normalize());
+            assert!((world.normal.length() - 1.0).abs() < 1e-4);
+
+            let p = world.position;
+            assert!(p.x() >= min.x() - 1e-3 && p.y() >= min.y() - 1e-3 && p.z() >= min.z() - 1e-3, "{:?} is below {:?}", p, min);
+            assert!(p.x() <= max.x() + 1e-3 && p.y() <= max.y() + 1e-3 && p.z() <= max.z() + 1e-3, "{:?} is above {:?}", p, max);
+        }
+        assert!(hits > 500, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn only_uniform_scales_are_similarities() {
+        let sphere : Arc<dyn Hitable + Send> = Arc::new(Sphere::new(Vec3::zero(), 1.0, 0));
+        let rotation = Quat::from_rotation_y(deg(30.0));
+        let uniform = Instance::from_scale_rotation_translation(sphere.clone(), Vec3::one() * 3.0, rotation, Vec3::new(1.0, 2.0, 3.0));
+        let stretched = Instance::from_scale_rotation_translation(sphere, Vec3::new(3.0, 1.0, 3.0), rotation, Vec3::new(1.0, 2.0, 3.0));
+        assert!(uniform.similarity);
+        assert!(!stretched.similarity);
+
+        //Seen from 10 units away a sphere of radius 3 fills the cone the uniform instance reports
+        let origin = Vec3::new(1.0, 2.0, 13.0);
+        let expected = 1.0 / (2.0 * std::f32::consts::PI * (1.0 - (1.0f32 - 0.09).sqrt()));
+        assert!((uniform.pdf_value(origin, Vec3::new(0.0, 0.0, -1.0), 0.0) - expected).abs() < 1e-2 * expected);
+        assert_eq!(stretched.pdf_value(origin, Vec3::new(0.0, 0.0, -1.0), 0.0), 0.0);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::glam::Vec3;
+
+use std::f32;
+
+//Lights with no surface, only reachable through shadow rays since nothing can bounce into them.
+//Falloff follows glTF punctual lights: inverse square, smoothly windowed to zero at range when one is given.
+pub enum Light {
+    Point { position: Vec3, intensity: Vec3, range: Option<f32> },
+    //Full strength inside inner_angle fading to nothing at outer_angle, both measured from direction in degrees
+    Spot { position: Vec3, direction: Vec3, intensity: Vec3, inner_angle: f32, outer_angle: f32, range: Option<f32> },
+    //Like the sun, parallel light travelling along direction with the same irradiance everywhere
+    Directional { direction: Vec3, irradiance: Vec3 }
+}
+
+#[inline]
+fn distance_falloff(distance: f32, range: Option<f32>) -> f32 {
+    let window = match range {
+        Some(range) => {
+            let ratio = distance / range;
+            (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0).powi(2)
+        },
+        None => 1.0
+    };
+    window / (distance * distance).max(1e-8)
+}
+
+impl Light {
+    //The direction from position toward the light, how far away it is and the irradiance it delivers
+    //to a surface facing it, None when position gets nothing from this light
+    pub fn illuminate(&self, position: Vec3) -> Option<(Vec3, f32, Vec3)> {
+        match self {
+            Light::Point { position: light_position, intensity, range } => {
+                let offset = *light_position - position;
+                let distance = offset.length();
+                let falloff = distance_falloff(distance, *range);
+                if distance <= 0.0 || falloff <= 0.0 {
+                    return None;
+                }
+                Some((offset / distance, distance, *intensity * falloff))
+            },
+            Light::Spot { position: light_position, direction, intensity, inner_angle, outer_angle, range } => {
+                let offset = *light_position - position;
+                let distance = offset.length();
+                if distance <= 0.0 {
+                    return None;
+                }
+                let wi = offset / distance;
+
+                let cos_outer = outer_angle.to_radians().cos();
+                let cos_inner = inner_angle.to_radians().cos();
+                let scale = 1.0 / (cos_inner - cos_outer).max(1e-3);
+                let cone = ((-wi).dot(*direction) * scale - cos_outer * scale).clamp(0.0, 1.0);
+                let falloff = cone * cone * distance_falloff(distance, *range);
+                if falloff <= 0.0 {
+                    return None;
+                }
+                Some((wi, distance, *intensity * falloff))
+            },
+            Light::Directional { direction, irradiance } => Some((-*direction, f32::MAX, *irradiance))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn irradiance(light: &Light, position: Vec3) -> f32 {
+        light.illuminate(position).map_or(0.0, |(_, _, irradiance)| irradiance.y())
+    }
+
+    #[test]
+    fn point_light_falls_off_with_the_square_of_distance() {
+        let light = Light::Point { position: Vec3::new(1.0, 2.0, 3.0), intensity: Vec3::one() * 8.0, range: None };
+        let (wi, distance, near) = light.illuminate(Vec3::new(1.0, 0.0, 3.0)).unwrap();
+        assert!((wi - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+        assert!((distance - 2.0).abs() < 1e-5);
+        assert!((near.y() - 2.0).abs() < 1e-5);
+        for distance in &[1.0, 4.0, 10.0] {
+            let far = irradiance(&light, Vec3::new(1.0, 2.0 - distance, 3.0));
+            assert!((far - 8.0 / (distance * distance)).abs() < 1e-4, "{} away gets {}", distance, far);
+        }
+    }
+
+    #[test]
+    fn range_fades_a_point_light_out() {
+        let light = Light::Point { position: Vec3::zero(), intensity: Vec3::one(), range: Some(5.0) };
+        assert!(irradiance(&light, Vec3::new(4.0, 0.0, 0.0)) < 1.0 / 16.0);
+        assert!(light.illuminate(Vec3::new(5.0, 0.0, 0.0)).is_none());
+        assert!(light.illuminate(Vec3::new(0.0, -6.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn spot_light_is_dark_outside_its_outer_angle() {
+        let light = Light::Spot {
+            position: Vec3::new(0.0, 4.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            intensity: Vec3::one() * 16.0,
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            range: None
+        };
+        let at_angle = |degrees: f32| Vec3::new(4.0 * degrees.to_radians().tan(), 0.0, 0.0);
+        let center = irradiance(&light, Vec3::zero());
+        assert!((center - 1.0).abs() < 1e-4);
+        //Inside the inner cone only the distance matters
+        let inner = irradiance(&light, at_angle(15.0));
+        let distance_squared = 16.0 + at_angle(15.0).x().powi(2);
+        assert!((inner - 16.0 / distance_squared).abs() < 1e-4);
+        //Fading between the cones, then nothing past the outer one
+        let between = irradiance(&light, at_angle(25.0));
+        assert!(between > 0.0 && between < 16.0 / (16.0 + at_angle(25.0).x().powi(2)));
+        assert!(light.illuminate(at_angle(31.0)).is_none());
+        assert!(light.illuminate(Vec3::new(0.0, 8.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn directional_light_is_the_same_everywhere() {
+        let light = Light::Directional { direction: Vec3::new(0.0, -1.0, 0.0), irradiance: Vec3::one() * 3.0 };
+        for position in &[Vec3::zero(), Vec3::new(100.0, -50.0, 7.0)] {
+            let (wi, _, irradiance) = light.illuminate(*position).unwrap();
+            assert_eq!(wi, Vec3::new(0.0, 1.0, 0.0));
+            assert_eq!(irradiance, Vec3::one() * 3.0);
+        }
+    }
+}
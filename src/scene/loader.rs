@@ -0,0 +1,786 @@
+use crate::scene::*;
+use crate::controls::CameraSettings;
+use crate::render::RenderSettings;
+use crate::glam::{Vec3, Quat, deg};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+Scene files are plain text, one directive per line, # starts a comment.
+Every directive is a keyword, then any positional arguments, then key=value properties.
+Vectors are written as three comma separated numbers and values with spaces can be quoted.
+
+    render width=480 height=320 samples=4 frames=16
+    camera look_from=0,1,2 look_at=0,0,-1 vfov=60 shutter_open=0 shutter_close=1
+    environment map file=skies/studio.hdr intensity=1 rotate=90
+    texture marble noise color=0.9,0.9,0.9 scale=4 style=marble
+    texture tiles checker even=marble odd=0.1,0.1,0.1 scale=2 space=solid
+    texture earth image file=textures/earth.png wrap=repeat
+    material checker checkerboard albedo=0.1,0.7,0.3
+    material gold metal albedo=0.8,0.6,0.2 fuzz=0.3
+    material plastic microfacet base_color=0.8,0.1,0.1 roughness=0.3 metallic=0 specular=0.5
+    material floor lambertian albedo=tiles bump=marble bump_scale=0.02
+    sphere center=0,0,-1 radius=0.5 material=checker
+    moving_sphere center0=1,0,-1 center1=1,0.5,-1 time0=0 time1=1 radius=0.5 material=gold
+    box min=-1,-1,-1 max=1,1,1 rotate=0,45,0 translate=2,0,-3 material=gold
+    plane point=0,-0.5,0 normal=0,1,0 material=checker
+    light point position=0,3,0 color=1,0.9,0.8 intensity=20 range=10
+    light spot position=2,3,0 direction=-1,-1,-1 intensity=30 inner_angle=15 outer_angle=25
+    light directional direction=-1,-2,-1 intensity=2
+    disk center=0,2,-1 normal=0,-1,0 radius=0.5 material=gold
+    rect min=-1,2,-2 max=1,2,0 material=gold
+    mesh file=models/teapot.obj material=gold name=teapot visible=false
+    instance teapot scale=0.5 rotate=0,90,0 translate=1,0,-2
+    material smoke isotropic albedo=0.8,0.8,0.8
+    sphere center=0,1,-2 radius=1 name=cloud visible=false
+    medium cloud density=2 material=smoke
+    fog density=0.05 color=0.9,0.9,1 g=0.3 distance=50
+
+Colors given to materials and checker textures can be x,y,z or the name of a texture.
+Any material can take normal_map=<texture> with normal_strength=, or bump=<texture> with bump_scale=.
+Image paths are relative to the scene file, like mesh paths.
+The environment is sky, gradient bottom= top=, map file=, daylight or color=, background is the same directive.
+daylight takes sun_direction=, turbidity= from 1.7 to 10, sun_size= in degrees across and sun=false to leave the sun disk out.
+Maps are equirectangular .hdr, .exr or any other image, rotate= turns them about y in degrees.
+Radii and sizes must be greater than zero, inward=true turns a sphere's normals inside so a glass one makes a hollow bubble.
+A rect is axis aligned, exactly one axis of min and max has to be equal.
+Any shape can be named and then placed again with instance, visible=false leaves the original out of the world.
+medium fills a named closed shape with smoke scattering by an isotropic or henyey_greenstein material, g= from -1 to 1 leans it backward or forward.
+fog fills the space between all surfaces, rays leaving the scene cross distance= of it, without one the environment is lost in it.
+
+Mesh paths are relative to the scene file. material= is used for faces the OBJ does not give a material.
+*/
+
+pub enum SceneError {
+    Io(io::Error),
+    Parse { line: usize, message: String }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{}", error),
+            SceneError::Parse { line, message } => write!(f, "line {}: {}", line, message)
+        }
+    }
+}
+
+pub fn load_scene(path: &Path, settings: &mut RenderSettings) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path).map_err(SceneError::Io)?;
+    parse_scene(&source, path.parent().unwrap_or_else(|| Path::new("")), settings)
+}
+
+pub fn parse_scene(source: &str, base_dir: &Path, settings: &mut RenderSettings) -> Result<Scene, SceneError> {
+    let mut material_library = MaterialLibrary::new();
+    let mut hitable_library = HitableLibrary::new();
+    let mut camera = CameraSettings::new();
+    let mut environment = Environment::sky();
+    let mut materials : HashMap<String, MaterialID> = HashMap::new();
+    let mut textures : HashMap<String, Arc<dyn Texture>> = HashMap::new();
+    let mut hitable_id_list = vec![];
+    let mut shapes : HashMap<String, HitableID> = HashMap::new();
+    let mut lights = vec![];
+    let mut fog = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let mut directive = match Directive::parse(index + 1, text)? {
+            Some(directive) => directive,
+            None => continue
+        };
+
+        match directive.keyword.as_str() {
+            "render" => {
+                let line = directive.line;
+                for (key, value, used) in directive.properties.iter_mut() {
+                    settings.set(key, value).map_err(|e| SceneError::Parse { line, message: format!("render: {}", e) })?;
+                    *used = true;
+                }
+            },
+            "camera" => {
+                camera.look_from = directive.vec3("look_from", Some(camera.look_from))?;
+                camera.look_at = directive.vec3("look_at", Some(camera.look_at))?;
+                camera.up = directive.vec3("up", Some(camera.up))?;
+                camera.vfov = directive.float("vfov", Some(camera.vfov))?;
+                camera.aperture = directive.float("aperture", Some(camera.aperture))?;
+                camera.shutter_open = directive.float("shutter_open", Some(camera.shutter_open))?;
+                camera.shutter_close = directive.float("shutter_close", Some(camera.shutter_close))?;
+                if directive.has("focus_distance") {
+                    camera.focus_distance = Some(directive.float("focus_distance", None)?);
+                }
+                if (camera.look_from - camera.look_at).length() == 0.0 {
+                    return Err(directive.error_at(String::from("look_from and look_at must be different points")));
+                }
+                if (camera.look_from - camera.look_at).cross(camera.up).length() == 0.0 {
+                    return Err(directive.error_at(String::from("up must not point along the view direction")));
+                }
+                if camera.vfov <= 0.0 || camera.vfov >= 180.0 {
+                    return Err(directive.error_at(String::from("vfov must be between 0 and 180 degrees")));
+                }
+                if camera.aperture < 0.0 {
+                    return Err(directive.error_at(String::from("aperture must not be negative")));
+                }
+                if camera.focus_distance.is_some_and(|d| d <= 0.0) {
+                    return Err(directive.error_at(String::from("focus_distance must be greater than zero")));
+                }
+            },
+            "background" | "environment" => {
+                if directive.has("color") && !directive.arguments.is_empty() {
+                    return Err(directive.error_at(format!("color= can not be combined with '{}', it makes a constant environment", directive.arguments[0])));
+                }
+                environment = if directive.arguments.is_empty() && directive.has("color") {
+                    Environment::Constant(directive.vec3("color", None)?)
+                }
+                else {
+                    match directive.argument(0, "environment, sky, gradient, map, daylight or color=r,g,b")?.as_str() {
+                        "sky" => Environment::sky(),
+                        "gradient" => Environment::Gradient {
+                            bottom: directive.vec3("bottom", Some(Vec3::one()))?,
+                            top: directive.vec3("top", Some(Vec3::new(0.5, 0.7, 1.0)))?
+                        },
+                        "map" => {
+                            let file = base_dir.join(directive.required("file")?);
+                            let intensity = directive.float("intensity", Some(1.0))?;
+                            let rotation = directive.float("rotate", Some(0.0))?.to_radians();
+                            Environment::Map(EnvironmentMap::load(&file, intensity, rotation).map_err(|e| directive.error_at(e))?)
+                        },
+                        "daylight" => {
+                            let sun_direction = directive.vec3("sun_direction", Some(Vec3::new(0.5, 1.0, -0.5)))?;
+                            let turbidity = directive.float("turbidity", Some(3.0))?;
+                            let sun_size = directive.float("sun_size", Some(0.53))?;
+                            let intensity = directive.float("intensity", Some(1.0))?;
+                            let sun_size = if directive.boolean("sun", true)? { sun_size } else { 0.0 };
+                            if sun_direction.length() == 0.0 || sun_direction.normalize().y() <= 0.0 {
+                                return Err(directive.error_at(String::from("sun_direction must point above the horizon")));
+                            }
+                            if !(1.7..=10.0).contains(&turbidity) {
+                                return Err(directive.error_at(String::from("turbidity must be between 1.7 and 10")));
+                            }
+                            if !(0.0..=20.0).contains(&sun_size) {
+                                return Err(directive.error_at(String::from("sun_size must be between 0 and 20 degrees")));
+                            }
+                            Environment::Daylight(Daylight::new(sun_direction, turbidity, sun_size, intensity))
+                        },
+                        kind => return Err(directive.error_at(format!("unknown environment '{}'", kind)))
+                    }
+                };
+            },
+            "texture" => {
+                let name = directive.argument(0, "texture name")?;
+                if textures.contains_key(&name) {
+                    return Err(directive.error(format!("texture '{}' is already defined", name)));
+                }
+                let kind = directive.argument(1, "texture type")?;
+                let texture = parse_texture(&mut directive, &kind, &textures, base_dir)?;
+                textures.insert(name, texture);
+            },
+            "fog" => {
+                let density = directive.positive("density", None)?;
+                let color = directive.vec3("color", Some(Vec3::one()))?;
+                let g = anisotropy(&mut directive)?;
+                let distance = if directive.has("distance") { Some(directive.positive("distance", None)?) } else { None };
+                fog = Some(Fog::new(density, distance, Phase::new(color, g)));
+            },
+            "light" => {
+                let kind = directive.argument(0, "light type, point, spot or directional")?;
+                lights.push(parse_light(&mut directive, &kind)?);
+            },
+            "material" => {
+                let name = directive.argument(0, "material name")?;
+                if materials.contains_key(&name) {
+                    return Err(directive.error(format!("material '{}' is already defined", name)));
+                }
+                let kind = directive.argument(1, "material type")?;
+                let material = parse_material(&mut directive, &kind, &textures)?;
+                materials.insert(name, material_library.add_new(material));
+            },
+            "sphere" => {
+                let center = directive.vec3("center", None)?;
+                let radius = directive.positive("radius", None)?;
+                let radius = if directive.boolean("inward", false)? { -radius } else { radius };
+                let material = directive.material("material", &materials)?;
+                add_shape(&mut directive, Box::new(Sphere::new(center, radius, material)), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "moving_sphere" => {
+                let center0 = directive.vec3("center0", None)?;
+                let center1 = directive.vec3("center1", None)?;
+                let time0 = directive.float("time0", Some(0.0))?;
+                let time1 = directive.float("time1", Some(1.0))?;
+                let radius = directive.positive("radius", None)?;
+                let radius = if directive.boolean("inward", false)? { -radius } else { radius };
+                let material = directive.material("material", &materials)?;
+                add_shape(&mut directive, Box::new(MovingSphere::new(center0, center1, time0, time1, radius, material)), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "box" => {
+                let min = directive.vec3("min", None)?;
+                let max = directive.vec3("max", None)?;
+                let material = directive.material("material", &materials)?;
+                if min.x() == max.x() || min.y() == max.y() || min.z() == max.z() {
+                    return Err(directive.error_at(String::from("min and max must differ on every axis")));
+                }
+                let mut cube = Cube::new(min, max, material);
+                if directive.has("rotate") || directive.has("translate") {
+                    let rotation = directive.rotation("rotate")?;
+                    let translation = directive.vec3("translate", Some(Vec3::zero()))?;
+                    cube = cube.with_transform(rotation, translation);
+                }
+                add_shape(&mut directive, Box::new(cube), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "plane" => {
+                let point = directive.vec3("point", None)?;
+                let normal = directive.vec3("normal", Some(Vec3::new(0.0, 1.0, 0.0)))?;
+                let material = directive.material("material", &materials)?;
+                if normal.length() == 0.0 {
+                    return Err(directive.error_at(String::from("normal must not be zero")));
+                }
+                add_shape(&mut directive, Box::new(Plane::new(point, normal, material)), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "disk" => {
+                let center = directive.vec3("center", None)?;
+                let normal = directive.vec3("normal", Some(Vec3::new(0.0, 1.0, 0.0)))?;
+                let radius = directive.positive("radius", None)?;
+                let material = directive.material("material", &materials)?;
+                if normal.length() == 0.0 {
+                    return Err(directive.error_at(String::from("normal must not be zero")));
+                }
+                add_shape(&mut directive, Box::new(Disk::new(center, normal, radius, material)), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "rect" => {
+                let min = directive.vec3("min", None)?;
+                let max = directive.vec3("max", None)?;
+                let material = directive.material("material", &materials)?;
+                let rect : Box<dyn Hitable + Send> = match (min.x() == max.x(), min.y() == max.y(), min.z() == max.z()) {
+                    (false, false, true) => Box::new(XYRect::new(min.x(), max.x(), min.y(), max.y(), min.z(), material)),
+                    (false, true, false) => Box::new(XZRect::new(min.x(), max.x(), min.z(), max.z(), min.y(), material)),
+                    (true, false, false) => Box::new(YZRect::new(min.y(), max.y(), min.z(), max.z(), min.x(), material)),
+                    _ => return Err(directive.error_at(String::from("exactly one axis of min and max must be equal")))
+                };
+                add_shape(&mut directive, rect, &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "mesh" => {
+                let file = base_dir.join(directive.required("file")?);
+                let material = if directive.has("material") { Some(directive.material("material", &materials)?) } else { None };
+                let mesh = load_obj(&file, &mut material_library, &materials, material).map_err(|e| directive.error_at(e))?;
+                add_shape(&mut directive, Box::new(mesh), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "instance" => {
+                let name = directive.argument(0, "name of the shape to instance")?;
+                let object = match shapes.get(&name).and_then(|id| hitable_library.share_hitable(*id)) {
+                    Some(object) => object,
+                    None => return Err(directive.error_at(format!("unknown shape '{}'", name)))
+                };
+                let scale = directive.vec3("scale", Some(Vec3::one()))?;
+                let rotation = directive.rotation("rotate")?;
+                let translation = directive.vec3("translate", Some(Vec3::zero()))?;
+                if scale.x() == 0.0 || scale.y() == 0.0 || scale.z() == 0.0 {
+                    return Err(directive.error_at(String::from("scale must not be zero on any axis")));
+                }
+                let instance = Instance::from_scale_rotation_translation(object, scale, rotation, translation);
+                add_shape(&mut directive, Box::new(instance), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            "medium" => {
+                let name = directive.argument(0, "name of the shape to fill")?;
+                let boundary = match shapes.get(&name).and_then(|id| hitable_library.share_hitable(*id)) {
+                    Some(boundary) => boundary,
+                    None => return Err(directive.error_at(format!("unknown shape '{}'", name)))
+                };
+                let density = directive.positive("density", None)?;
+                let material = directive.material("material", &materials)?;
+                add_shape(&mut directive, Box::new(ConstantMedium::new(boundary, density, material)), &mut hitable_library, &mut hitable_id_list, &mut shapes)?;
+            },
+            keyword => return Err(directive.error(format!("unknown directive '{}'", keyword)))
+        }
+
+        directive.finish()?;
+    }
+
+    let world = HitableList::new_with_hitable_id_list(hitable_id_list, &hitable_library);
+
+    let mut scene = Scene::new(material_library, hitable_library, world, camera, environment);
+    scene.lights = lights;
+    scene.fog = fog;
+    Ok(scene)
+}
+
+//Every shape can be given a name= for instances to refer to, visible=false keeps it out of the world
+fn add_shape(directive: &mut Directive, shape: Box<dyn Hitable + Send>, hitable_library: &mut HitableLibrary, hitable_id_list: &mut Vec<HitableID>, shapes: &mut HashMap<String, HitableID>) -> Result<(), SceneError> {
+    let name = directive.value("name");
+    let visible = directive.boolean("visible", true)?;
+    if let Some(name) = &name {
+        if shapes.contains_key(name) {
+            return Err(directive.error_at(format!("a shape named '{}' is already defined", name)));
+        }
+    }
+
+    let id = hitable_library.add_hitable_to_library(shape);
+    if visible {
+        hitable_id_list.push(id);
+    }
+    if let Some(name) = name {
+        shapes.insert(name, id);
+    }
+    Ok(())
+}
+
+fn parse_material(directive: &mut Directive, kind: &str, textures: &HashMap<String, Arc<dyn Texture>>) -> Result<Box<dyn Material + Send>, SceneError> {
+    let material : Box<dyn Material + Send> = match kind {
+        "lambertian" => Box::new(Lambertian::textured(directive.texture("albedo", None, textures)?)),
+        "checkerboard" => Box::new(CheckerBoard::textured(directive.texture("albedo", None, textures)?)),
+        "metal" => Box::new(Metal::textured(directive.texture("albedo", None, textures)?, directive.float("fuzz", Some(0.0))?)),
+        "dielectric" => Box::new(Deilectric::new(directive.float("ior", None)?)),
+        "diffuse_light" => Box::new(DiffuseLight::textured(directive.texture("emit", None, textures)?)),
+        "microfacet" => Box::new(Microfacet::textured(
+            directive.texture("base_color", None, textures)?,
+            directive.float("roughness", Some(0.5))?,
+            directive.float("metallic", Some(0.0))?,
+            directive.float("specular", Some(0.5))?
+        )),
+        "isotropic" => Box::new(Phase::isotropic(directive.texture("albedo", None, textures)?)),
+        "henyey_greenstein" => {
+            let albedo = directive.texture("albedo", None, textures)?;
+            Box::new(Phase::henyey_greenstein(albedo, anisotropy(directive)?))
+        },
+        _ => return Err(directive.error(format!("unknown material type '{}'", kind)))
+    };
+
+    if directive.has("normal_map") && directive.has("bump") {
+        return Err(directive.error_at(String::from("use either normal_map or bump, not both")));
+    }
+    let detail = if directive.has("normal_map") {
+        Some(SurfaceDetail::NormalMap {
+            texture: directive.texture("normal_map", None, textures)?,
+            strength: directive.float("normal_strength", Some(1.0))?
+        })
+    }
+    else if directive.has("bump") {
+        Some(SurfaceDetail::Bump {
+            height: directive.texture("bump", None, textures)?,
+            scale: directive.float("bump_scale", Some(0.05))?
+        })
+    }
+    else {
+        None
+    };
+
+    match detail {
+        Some(detail) => Ok(Box::new(BumpMapped::new(material, detail))),
+        None => Ok(material)
+    }
+}
+
+//The g of a Henyey-Greenstein phase function
+fn anisotropy(directive: &mut Directive) -> Result<f32, SceneError> {
+    let g = directive.float("g", Some(0.0))?;
+    if g <= -1.0 || g >= 1.0 {
+        return Err(directive.error_at(String::from("g must be between -1 and 1")));
+    }
+    Ok(g)
+}
+
+fn parse_light(directive: &mut Directive, kind: &str) -> Result<Light, SceneError> {
+    let color = directive.vec3("color", Some(Vec3::one()))?;
+    let intensity = color * directive.float("intensity", Some(1.0))?;
+    let range = if directive.has("range") { Some(directive.float("range", None)?) } else { None };
+    if range.is_some_and(|range| range <= 0.0) {
+        return Err(directive.error_at(String::from("range must be greater than zero")));
+    }
+
+    let light = match kind {
+        "point" => Light::Point {
+            position: directive.vec3("position", None)?,
+            intensity,
+            range
+        },
+        "spot" => {
+            let position = directive.vec3("position", None)?;
+            let direction = directive.vec3("direction", None)?;
+            let outer_angle = directive.float("outer_angle", Some(45.0))?;
+            let inner_angle = directive.float("inner_angle", Some(0.0))?;
+            if direction.length() == 0.0 {
+                return Err(directive.error_at(String::from("direction must not be zero")));
+            }
+            if outer_angle <= 0.0 || outer_angle > 90.0 || inner_angle < 0.0 || inner_angle > outer_angle {
+                return Err(directive.error_at(String::from("angles must satisfy 0 <= inner_angle <= outer_angle <= 90")));
+            }
+            Light::Spot { position, direction: direction.normalize(), intensity, inner_angle, outer_angle, range }
+        },
+        "directional" => {
+            let direction = directive.vec3("direction", None)?;
+            if direction.length() == 0.0 {
+                return Err(directive.error_at(String::from("direction must not be zero")));
+            }
+            if range.is_some() {
+                return Err(directive.error_at(String::from("directional lights have no range")));
+            }
+            Light::Directional { direction: direction.normalize(), irradiance: intensity }
+        },
+        _ => return Err(directive.error_at(format!("unknown light type '{}'", kind)))
+    };
+    Ok(light)
+}
+
+fn parse_texture(directive: &mut Directive, kind: &str, textures: &HashMap<String, Arc<dyn Texture>>, base_dir: &Path) -> Result<Arc<dyn Texture>, SceneError> {
+    let texture : Arc<dyn Texture> = match kind {
+        "constant" => Arc::new(ConstantTexture::new(directive.vec3("color", None)?)),
+        "checker" => {
+            let even = directive.texture("even", Some(Vec3::one()), textures)?;
+            let odd = directive.texture("odd", Some(Vec3::zero()), textures)?;
+            let scale = directive.float("scale", Some(1.0))?;
+            let space = match directive.value("space").as_deref() {
+                Some("solid") | None => CheckerSpace::Solid,
+                Some("uv") => CheckerSpace::Uv,
+                Some(space) => return Err(directive.error_at(format!("space expects solid or uv, found '{}'", space)))
+            };
+            Arc::new(CheckerTexture::new(even, odd, scale, space))
+        },
+        "image" => {
+            let file = base_dir.join(directive.required("file")?);
+            let wrap = match directive.value("wrap").as_deref() {
+                Some("repeat") | None => WrapMode::Repeat,
+                Some("mirror") => WrapMode::Mirror,
+                Some("clamp") => WrapMode::Clamp,
+                Some(wrap) => return Err(directive.error_at(format!("wrap expects repeat, mirror or clamp, found '{}'", wrap)))
+            };
+            Arc::new(ImageTexture::load(&file, wrap).map_err(|e| directive.error_at(e))?)
+        },
+        "noise" => {
+            let color = directive.vec3("color", Some(Vec3::one()))?;
+            let scale = directive.float("scale", Some(1.0))?;
+            let style = match directive.value("style").as_deref() {
+                Some("smooth") | None => NoiseStyle::Smooth,
+                Some("turbulence") => NoiseStyle::Turbulence,
+                Some("marble") => NoiseStyle::Marble,
+                Some(style) => return Err(directive.error_at(format!("style expects smooth, turbulence or marble, found '{}'", style)))
+            };
+            Arc::new(NoiseTexture::new(color, scale, style))
+        },
+        _ => return Err(directive.error(format!("unknown texture type '{}'", kind)))
+    };
+    Ok(texture)
+}
+
+struct Directive {
+    line: usize,
+    keyword: String,
+    arguments: Vec<String>,
+    properties: Vec<(String, String, bool)>
+}
+
+impl Directive {
+    fn parse(line: usize, text: &str) -> Result<Option<Directive>, SceneError> {
+        let text = match text.find('#') {
+            Some(comment) => &text[..comment],
+            None => text
+        };
+
+        let mut tokens = vec![];
+        let mut token = String::new();
+        let mut quoted = false;
+        for c in text.chars() {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            else if c.is_whitespace() && !quoted {
+                if !token.is_empty() {
+                    tokens.push(token);
+                    token = String::new();
+                }
+            }
+            else {
+                token.push(c);
+            }
+        }
+        if quoted {
+            return Err(SceneError::Parse { line, message: String::from("unterminated quote") });
+        }
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let mut directive = Directive {
+            line,
+            keyword: tokens.remove(0),
+            arguments: vec![],
+            properties: vec![]
+        };
+
+        for token in tokens {
+            match token.find('=') {
+                Some(split) => {
+                    let key = token[..split].to_string();
+                    if directive.properties.iter().any(|(k, _, _)| *k == key) {
+                        return Err(directive.error(format!("'{}' is given more than once", key)));
+                    }
+                    directive.properties.push((key, token[split + 1..].to_string(), false));
+                },
+                None => {
+                    if !directive.properties.is_empty() {
+                        return Err(directive.error(format!("'{}' is missing a value, expected key=value", token)));
+                    }
+                    directive.arguments.push(token);
+                }
+            }
+        }
+
+        Ok(Some(directive))
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError::Parse { line: self.line, message }
+    }
+
+    fn error_at(&self, message: String) -> SceneError {
+        self.error(format!("{}: {}", self.keyword, message))
+    }
+
+    fn argument(&self, index: usize, what: &str) -> Result<String, SceneError> {
+        match self.arguments.get(index) {
+            Some(argument) => Ok(argument.clone()),
+            None => Err(self.error_at(format!("expected a {}", what)))
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.properties.iter().any(|(k, _, _)| k == key)
+    }
+
+    fn value(&mut self, key: &str) -> Option<String> {
+        for (k, value, used) in self.properties.iter_mut() {
+            if k == key {
+                *used = true;
+                return Some(value.clone());
+            }
+        }
+        None
+    }
+
+    fn required(&mut self, key: &str) -> Result<String, SceneError> {
+        match self.value(key) {
+            Some(value) => Ok(value),
+            None => Err(self.error_at(format!("missing {}", key)))
+        }
+    }
+
+    fn float(&mut self, key: &str, default: Option<f32>) -> Result<f32, SceneError> {
+        match (self.value(key), default) {
+            (Some(value), _) => value.parse::<f32>().map_err(|_| self.error_at(format!("{} expects a number, found '{}'", key, value))),
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(self.error_at(format!("missing {}", key)))
+        }
+    }
+
+    //Sizes and densities, where zero or less would break the shape
+    fn positive(&mut self, key: &str, default: Option<f32>) -> Result<f32, SceneError> {
+        let value = self.float(key, default)?;
+        if value <= 0.0 {
+            return Err(self.error_at(format!("{} must be greater than zero, found {}", key, value)));
+        }
+        Ok(value)
+    }
+
+    fn vec3(&mut self, key: &str, default: Option<Vec3>) -> Result<Vec3, SceneError> {
+        match (self.value(key), default) {
+            (Some(value), _) => parse_vec3(&value).ok_or_else(|| self.error_at(format!("{} expects x,y,z, found '{}'", key, value))),
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(self.error_at(format!("missing {}", key)))
+        }
+    }
+
+    //Either a color written like a vec3 or the name of a texture defined earlier
+    fn texture(&mut self, key: &str, default: Option<Vec3>, textures: &HashMap<String, Arc<dyn Texture>>) -> Result<Arc<dyn Texture>, SceneError> {
+        match (self.value(key), default) {
+            (Some(value), _) => {
+                if let Some(texture) = textures.get(&value) {
+                    return Ok(texture.clone());
+                }
+                match parse_vec3(&value) {
+                    Some(color) => Ok(Arc::new(ConstantTexture::new(color))),
+                    None => Err(self.error_at(format!("{} expects x,y,z or a texture name, found '{}'", key, value)))
+                }
+            },
+            (None, Some(default)) => Ok(Arc::new(ConstantTexture::new(default))),
+            (None, None) => Err(self.error_at(format!("missing {}", key)))
+        }
+    }
+
+    fn boolean(&mut self, key: &str, default: bool) -> Result<bool, SceneError> {
+        match self.value(key).as_deref() {
+            Some("true") => Ok(true),
+            Some("false") => Ok(false),
+            Some(value) => Err(self.error_at(format!("{} expects true or false, found '{}'", key, value))),
+            None => Ok(default)
+        }
+    }
+
+    //Degrees about x, then y, then z
+    fn rotation(&mut self, key: &str) -> Result<Quat, SceneError> {
+        let angles = self.vec3(key, Some(Vec3::zero()))?;
+        let x = Quat::from_rotation_x(deg(angles.x()));
+        let y = Quat::from_rotation_y(deg(angles.y()));
+        let z = Quat::from_rotation_z(deg(angles.z()));
+        Ok(z.mul_quat(y).mul_quat(x))
+    }
+
+    fn material(&mut self, key: &str, materials: &HashMap<String, MaterialID>) -> Result<MaterialID, SceneError> {
+        let name = self.required(key)?;
+        match materials.get(&name) {
+            Some(id) => Ok(*id),
+            None => Err(self.error_at(format!("unknown material '{}'", name)))
+        }
+    }
+
+    //Catches misspelled properties instead of silently using the default
+    fn finish(&self) -> Result<(), SceneError> {
+        for (key, _, used) in &self.properties {
+            if !used {
+                return Err(self.error_at(format!("unknown property '{}'", key)));
+            }
+        }
+        Ok(())
+    }
+}
+
+//x,y,z or a single number used for all three
+fn parse_vec3(value: &str) -> Option<Vec3> {
+    let parts : Vec<Option<f32>> = value.split(',').map(|p| p.trim().parse::<f32>().ok()).collect();
+    match parts.as_slice() {
+        [Some(x), Some(y), Some(z)] => Some(Vec3::new(*x, *y, *z)),
+        [Some(s)] => Some(Vec3::one() * *s),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Ray;
+
+    fn parse(source: &str) -> Result<(Scene, RenderSettings), SceneError> {
+        let mut settings = RenderSettings::new();
+        let scene = parse_scene(source, Path::new(""), &mut settings)?;
+        Ok((scene, settings))
+    }
+
+    fn parse_error(source: &str) -> String {
+        match parse(source) {
+            Ok(_) => panic!("scene parsed without an error"),
+            Err(error) => error.to_string()
+        }
+    }
+
+    #[test]
+    fn parses_a_valid_scene() {
+        let source = "
+            # comment lines and blank lines are skipped
+            render width=64 height=32 samples=2
+
+            camera look_from=0,1,2 look_at=0,0,-1 vfov=60
+            environment gradient bottom=1,1,1 top=0.2,0.3,0.9
+            texture tiles checker even=1,1,1 odd=0,0,0 scale=2
+            material floor lambertian albedo=tiles
+            material lamp diffuse_light emit=4,4,4
+            plane point=0,-0.5,0 normal=0,1,0 material=floor
+            sphere center=0,0,-1 radius=0.5 material=lamp name=ball
+            instance ball translate=1,0,0
+            light point position=0,3,0 intensity=10
+        ";
+        let (scene, settings) = match parse(source) {
+            Ok(parsed) => parsed,
+            Err(error) => panic!("{}", error)
+        };
+
+        assert_eq!((settings.width, settings.height, settings.samples), (64, 32, 2));
+        assert_eq!(scene.world.ids().len(), 3);
+        assert_eq!(scene.emitters.len(), 2);
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.camera.vfov, 60.0);
+    }
+
+    #[test]
+    fn reports_unknown_directives() {
+        assert_eq!(parse_error("render width=8\nteapot size=2"), "line 2: unknown directive 'teapot'");
+    }
+
+    #[test]
+    fn reports_unused_properties() {
+        assert_eq!(
+            parse_error("material red lambertian albedo=1,0,0\nsphere center=0,0,0 radius=1 material=red colour=1,1,1"),
+            "line 2: sphere: unknown property 'colour'"
+        );
+    }
+
+    #[test]
+    fn reports_bad_vectors() {
+        assert_eq!(
+            parse_error("material red lambertian albedo=1,0\n"),
+            "line 1: material: albedo expects x,y,z or a texture name, found '1,0'"
+        );
+        assert_eq!(
+            parse_error("\n\ncamera look_from=0,one,2"),
+            "line 3: camera: look_from expects x,y,z, found '0,one,2'"
+        );
+    }
+
+    #[test]
+    fn reports_shapes_without_size() {
+        let material = "material red lambertian albedo=1,0,0\n";
+        assert_eq!(
+            parse_error(&format!("{}sphere center=0,0,0 radius=0 material=red", material)),
+            "line 2: sphere: radius must be greater than zero, found 0"
+        );
+        assert_eq!(
+            parse_error(&format!("{}\nsphere center=0,0,0 radius=-0.5 material=red", material)),
+            "line 3: sphere: radius must be greater than zero, found -0.5"
+        );
+        assert_eq!(
+            parse_error(&format!("{}moving_sphere center0=0,0,0 center1=1,0,0 radius=-1 material=red", material)),
+            "line 2: moving_sphere: radius must be greater than zero, found -1"
+        );
+        assert_eq!(
+            parse_error(&format!("{}disk center=0,0,0 radius=0 material=red", material)),
+            "line 2: disk: radius must be greater than zero, found 0"
+        );
+        assert_eq!(
+            parse_error(&format!("{}box min=0,0,0 max=1,0,1 material=red", material)),
+            "line 2: box: min and max must differ on every axis"
+        );
+        assert_eq!(
+            parse_error(&format!("{}rect min=0,0,0 max=1,0,0 material=red", material)),
+            "line 2: rect: exactly one axis of min and max must be equal"
+        );
+        assert_eq!(parse_error("fog density=-1"), "line 1: fog: density must be greater than zero, found -1");
+    }
+
+    #[test]
+    fn inward_spheres_flip_their_normals() {
+        let scene = match parse("material glass dielectric ior=1.5\nsphere center=0,0,0 radius=1 inward=true material=glass") {
+            Ok((scene, _)) => scene,
+            Err(error) => panic!("{}", error)
+        };
+        let mut record = HitRecord::empty();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(scene.world.cast_ray_into_world(&ray, 0.001, f32::MAX, &mut record, &scene.hitable_library));
+        assert!((record.normal - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn reports_unknown_materials() {
+        assert_eq!(parse_error("sphere center=0,0,0 radius=1 material=gold"), "line 1: sphere: unknown material 'gold'");
+    }
+
+    #[test]
+    fn rejects_conflicting_environments() {
+        assert_eq!(
+            parse_error("environment sky color=1,1,1"),
+            "line 1: environment: color= can not be combined with 'sky', it makes a constant environment"
+        );
+    }
+}
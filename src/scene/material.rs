@@ -1,10 +1,12 @@
 use crate::math::*;
 use crate::glam::Vec3;
-use crate::scene::{HitRecord, Hitable};
+use crate::scene::{HitRecord, Hitable, Texture, ConstantTexture, CheckerSpace, checker_is_even};
 
 use crate::rand::{thread_rng, Rng};
 
 use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
 
 pub type MaterialID = u32;
 
@@ -34,10 +36,14 @@ impl MaterialLibrary {
     
 }
 
+//attenuation is the sample's full weight, the BSDF times the cosine over pdf.
+//Specular scatters come from a delta lobe that eval and pdf can not see, their pdf is left at 0.
 pub struct ScatterHit {
     pub result: bool,
     pub attenuation:Vec3,
-    pub scattered: Ray
+    pub scattered: Ray,
+    pub pdf: f32,
+    pub specular: bool
 }
 
 impl ScatterHit {
@@ -45,22 +51,77 @@ impl ScatterHit {
         ScatterHit {
             result,
             attenuation,
-            scattered
+            scattered,
+            pdf: 0.0,
+            specular: true
         }
     }
+
+    //A direction drawn from a density the material can also report through pdf
+    pub fn sampled(attenuation: Vec3, scattered: Ray, pdf: f32) -> ScatterHit {
+        ScatterHit {
+            result: true,
+            attenuation,
+            scattered,
+            pdf,
+            specular: false
+        }
+    }
+
+    pub fn absorbed() -> ScatterHit {
+        ScatterHit::new(false, Vec3::zero(), Ray::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0)))
+    }
 }
 
+//Normal on the side wo arrives from, for surfaces that reflect the same from either side
+#[inline]
+pub fn facing_normal(record: &HitRecord, wo: Vec3) -> Vec3 {
+    if record.normal.dot(wo) < 0.0 { -record.normal } else { record.normal }
+}
+
+//wo points back along the incoming ray and wi toward where light arrives from, both unit length and away from the surface
 pub trait Material {
+    //Runs before emitted and scatter so surface detail like normal maps can bend the shading normal
+    fn perturb_normal(&self, _record: &mut HitRecord) {}
+
     fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &Hitable) -> ScatterHit;
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3;
+
+    //BSDF value without the cosine, zero for purely specular materials
+    fn eval(&self, _record: &HitRecord, _wo: Vec3, _wi: Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    //Solid angle density of scatter picking wi, zero for purely specular materials
+    fn pdf(&self, _record: &HitRecord, _wo: Vec3, _wi: Vec3) -> f32 {
+        0.0
+    }
+
+    //Radiance given off by the surface itself, added along the path on top of whatever it scatters
+    fn emitted(&self, _record: &HitRecord) -> Vec3 {
+        Vec3::zero()
+    }
+
+    //Shapes with an emissive material are sampled directly as lights
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    //Phase functions scatter the same whichever way light arrives, so direct light at them is not weighed by a cosine
+    fn is_medium(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
-    pub albedo:Vec3,
+    pub albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo:Vec3) -> Lambertian {
+        Lambertian::textured(Arc::new(ConstantTexture::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
         Lambertian {
             albedo
         }
@@ -68,104 +129,91 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, record: &HitRecord, hitable: &Hitable) -> ScatterHit {
-        let target = record.position + record.normal + random_in_unit_sphere();
-        ScatterHit::new(
-            true,
-            self.albedo.clone(),
-            Ray::new(record.position, target - record.position)
-        )
+    //Cosine weighted, which cancels the cosine and the pi in the BSDF and leaves only the albedo
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, _hitable: &dyn Hitable) -> ScatterHit {
+        let wo = -ray_in.get_direction().normalize();
+        let normal = facing_normal(record, wo);
+        let wi = to_world(random_cosine_direction(), normal);
+        let pdf = normal.dot(wi) / PI;
+        if pdf <= 0.0 {
+            return ScatterHit::absorbed();
+        }
+        ScatterHit::sampled(self.albedo.value(record.u, record.v, record.position), Ray::new(record.position, wi), pdf)
     }
 
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3 {
-        return self.albedo;
+    fn eval(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if facing_normal(record, wo).dot(wi) <= 0.0 {
+            return Vec3::zero();
+        }
+        self.albedo.value(record.u, record.v, record.position) / PI
     }
-}
 
+    fn pdf(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        facing_normal(record, wo).dot(wi).max(0.0) / PI
+    }
+}
 
+//Diffuse albedo checks alternating with a blue mirror across the surface's uv
 pub struct CheckerBoard {
-    pub albedo:Vec3,
-    pub metal_material: Metal
+    pub diffuse: Lambertian,
+    pub metal_material: Metal,
+    pub scale: Vec3,
+    pub space: CheckerSpace
 }
 
 impl CheckerBoard {
     pub fn new(albedo:Vec3) -> CheckerBoard {
+        CheckerBoard::textured(Arc::new(ConstantTexture::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> CheckerBoard {
         CheckerBoard {
-            albedo,
-            metal_material: Metal::new(Vec3::new(0.3f32, 0.432, 0.7f32), 0.0f32)
+            diffuse: Lambertian::textured(albedo),
+            metal_material: Metal::new(Vec3::new(0.3f32, 0.432, 0.7f32), 0.0f32),
+            //Five checks around and ten from pole to pole, as the board has always had
+            scale: Vec3::new(5.0, 10.0, 1.0),
+            space: CheckerSpace::Uv
         }
     }
 }
 
-impl Material for CheckerBoard {
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &Hitable) -> ScatterHit {
-    
-        let d = (hitable.get_center() - record.position).normalize();
-        let u = 0.5f32 + (d.z().atan2(d.x()) / 2.0f32 * std::f64::consts::PI as f32);
-        let v = 0.5f32 + (d.y().asin() / std::f64::consts::PI as f32);
-
-        if (u * 5.0f32) as i32 % 2 == 0 {
-            if (v * 10.0f32) as i32 % 2 == 0 {
-                let target = record.position + record.normal + random_in_unit_sphere();
-                ScatterHit::new(
-                    true,
-                    self.albedo.clone(),
-                    Ray::new(record.position, target - record.position)
-                )
-
-            }
-            else {
-                return self.metal_material.scatter(ray_in, record, hitable);
-            }
+impl CheckerBoard {
+    #[inline]
+    fn pick(&self, record: &HitRecord) -> &dyn Material {
+        if checker_is_even(record.u, record.v, record.position, self.scale, self.space) {
+            &self.diffuse
         }
         else {
-            if (v * 10.0f32) as i32 % 2 == 0 {
-                return self.metal_material.scatter(ray_in, record, hitable);
-            }
-            else {
-                let target = record.position + record.normal + random_in_unit_sphere();
-                ScatterHit::new(
-                    true,
-                    self.albedo.clone(),
-                    Ray::new(record.position, target - record.position)
-                )
-            }
+            &self.metal_material
         }
-
     }
+}
 
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3 {
+impl Material for CheckerBoard {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &dyn Hitable) -> ScatterHit {
+        self.pick(record).scatter(ray_in, record, hitable)
+    }
 
-        let d = (hitable.get_center() - record.position).normalize();
-        let u = 0.5f32 + (d.z().atan2(d.x()) / 2.0f32 * std::f64::consts::PI as f32);
-        let v = 0.5f32 + (d.y().asin() / std::f64::consts::PI as f32);
+    fn eval(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.pick(record).eval(record, wo, wi)
+    }
 
-        if (u * 5.0f32) as i32 % 2 == 0 {
-            if (v * 10.0f32) as i32 % 2 == 0 {
-                return Vec3::new(1.0, 0.0, 0.0);
-            }
-            else {
-                return self.metal_material.color(record, hitable);
-            }
-        }
-        else {
-            if (v * 10.0f32) as i32 % 2 == 0 {
-                return self.metal_material.color(record, hitable);
-            }
-            else {
-                return Vec3::new(1.0, 0.0, 0.0);        
-            }
-        }
+    fn pdf(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.pick(record).pdf(record, wo, wi)
     }
 }
 
 pub struct Metal {
-    pub albedo:Vec3,
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f32,
 }
 
 impl Metal {
     pub fn new(albedo:Vec3, fuzz: f32) -> Metal {
+        Metal::textured(Arc::new(ConstantTexture::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f32) -> Metal {
         let mut f = fuzz;
         if fuzz > 1.0 {
             f = 1.0;
@@ -182,33 +230,39 @@ impl Material for Metal {
     fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &Hitable) -> ScatterHit {
         let reflected = reflect(ray_in.get_direction().normalize(), record.normal);
         let scattered = Ray::new(record.position, reflected + random_in_unit_sphere() * self.fuzz);
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(record.u, record.v, record.position);
         let result = scattered.get_direction().dot(record.normal) > 0.0;
         return ScatterHit::new(result, attenuation, scattered);
     }
-
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3 {
-        return self.albedo;
-    }
 }
 
-pub struct Sky {
+pub struct DiffuseLight {
+    pub emit: Arc<dyn Texture>
 }
 
-impl Sky {
-    pub fn new() -> Sky {
-        Sky {}
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> DiffuseLight {
+        DiffuseLight::textured(Arc::new(ConstantTexture::new(emit)))
+    }
+
+    pub fn textured(emit: Arc<dyn Texture>) -> DiffuseLight {
+        DiffuseLight {
+            emit
+        }
     }
 }
 
-impl Material for Sky {
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord, hitable: &Hitable) -> ScatterHit {
-        return ScatterHit::new(false, Vec3::new(1.0, 1.0, 1.0), Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)));
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _record: &HitRecord, _hitable: &dyn Hitable) -> ScatterHit {
+        ScatterHit::absorbed()
     }
 
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3 {
-        let t = 0.5 * (record.normal.y() + 1.0);
-        return Vec3::new(1.0f32, 1.0f32, 1.0f32) * (1.0 - t) + Vec3::new(0.5, 0.7, 1.0) * t;//background color, I Think?    
+    fn emitted(&self, record: &HitRecord) -> Vec3 {
+        self.emit.value(record.u, record.v, record.position)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
     }
 }
 
@@ -280,8 +334,4 @@ impl Material for Deilectric {
             scattered
         )   
     }
-
-    fn color(&self, record: &HitRecord, hitable: &Hitable) -> Vec3 {
-        return Vec3::new(1.0, 1.0, 1.0);
-    }
 }
@@ -0,0 +1,276 @@
+use crate::scene::{Hitable, HitRecord, HitableID, MaterialID, Material, ScatterHit, Texture, ConstantTexture};
+use crate::math::{Ray, to_world};
+use crate::rand::{thread_rng, Rng};
+use crate::glam::Vec3;
+use crate::bvh::aabb::AABB;
+
+use std::f32;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+//How light scattering inside a medium spreads out, as a material for the points a ConstantMedium or fog picks.
+//g runs from -1 throwing light back the way it came through 0 for isotropic to 1 carrying it straight on.
+pub struct Phase {
+    pub albedo: Arc<dyn Texture>,
+    pub g: f32
+}
+
+impl Phase {
+    pub fn isotropic(albedo: Arc<dyn Texture>) -> Phase {
+        Phase::henyey_greenstein(albedo, 0.0)
+    }
+
+    pub fn henyey_greenstein(albedo: Arc<dyn Texture>, g: f32) -> Phase {
+        Phase {
+            albedo,
+            g
+        }
+    }
+
+    pub fn new(albedo: Vec3, g: f32) -> Phase {
+        Phase::henyey_greenstein(Arc::new(ConstantTexture::new(albedo)), g)
+    }
+
+    //cosine is between the direction light travelled in and the one it leaves along
+    #[inline]
+    fn value(&self, cosine: f32) -> f32 {
+        let denominator = 1.0 + self.g * self.g - 2.0 * self.g * cosine;
+        (1.0 - self.g * self.g) / (4.0 * PI * denominator * denominator.max(1e-8).sqrt())
+    }
+
+    //A new direction for light travelling along direction and its pdf, which is the phase function itself
+    pub fn sample(&self, direction: Vec3) -> (Vec3, f32) {
+        let mut rng = thread_rng();
+        let r1 : f32 = rng.gen_range(0.0, 1.0);
+        let r2 : f32 = rng.gen_range(0.0, 1.0);
+        let cosine = if self.g.abs() < 1e-3 {
+            1.0 - 2.0 * r1
+        }
+        else {
+            let square = (1.0 - self.g * self.g) / (1.0 - self.g + 2.0 * self.g * r1);
+            ((1.0 + self.g * self.g - square * square) / (2.0 * self.g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let phi = 2.0 * PI * r2;
+        let scattered = to_world(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cosine), direction.normalize());
+        (scattered, self.value(cosine))
+    }
+}
+
+impl Material for Phase {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, _hitable: &dyn Hitable) -> ScatterHit {
+        let (direction, pdf) = self.sample(ray_in.direction);
+        ScatterHit::sampled(self.albedo.value(record.u, record.v, record.position), Ray::new(record.position, direction), pdf)
+    }
+
+    fn eval(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.albedo.value(record.u, record.v, record.position) * self.value(-wo.dot(wi))
+    }
+
+    fn pdf(&self, _record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.value(-wo.dot(wi))
+    }
+
+    fn is_medium(&self) -> bool {
+        true
+    }
+}
+
+//Distance travelled through a medium of the given density before something scatters, exponentially distributed
+#[inline]
+fn free_flight(density: f32) -> f32 {
+    let r : f32 = thread_rng().gen_range(0.0, 1.0);
+    -(1.0 - r).ln() / density
+}
+
+//Smoke or cloud filling a shape. Rays crossing it scatter at a random depth so it is partly see through,
+//the boundary should be closed and is assumed convex, a ray enters and leaves it once.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hitable + Send>,
+    //Chance per unit of distance that light scatters
+    density: f32,
+    material_id: MaterialID,
+    id: HitableID
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hitable + Send>, density: f32, material_id: MaterialID) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            material_id,
+            id: 0
+        }
+    }
+}
+
+impl Hitable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        //Found over the whole line so rays starting inside still know where the medium began
+        let mut entry = HitRecord::empty();
+        let mut exit = HitRecord::empty();
+        if !self.boundary.hit(ray, -f32::MAX, f32::MAX, &mut entry) || !self.boundary.hit(ray, entry.t + 1e-4, f32::MAX, &mut exit) {
+            return false;
+        }
+
+        let start = entry.t.max(t_min);
+        let end = exit.t.min(t_max);
+        if start >= end {
+            return false;
+        }
+
+        let length = ray.direction.length();
+        let travelled = free_flight(self.density);
+        if travelled >= (end - start) * length {
+            return false;
+        }
+
+        record.t = start + travelled / length;
+        record.position = ray.point_at_paramater(record.t);
+        //Media have no surface, phase functions ignore the normal
+        record.normal = -ray.direction / length;
+        record.material = self.material_id;
+        record.hitable = self.id;
+        record.u = 0.0;
+        record.v = 0.0;
+        record.set_tangent_frame(Vec3::zero(), Vec3::zero());
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        self.boundary.quick_hit(ray)
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        self.boundary.get_center()
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.boundary.get_radius()
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.boundary.bounding_box()
+    }
+
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+}
+
+//Thin homogeneous haze between every surface in the scene.
+//Rays that leave the scene or head for the environment and directional lights cross at most distance of it,
+//without a distance the fog goes on forever and hides the environment completely.
+pub struct Fog {
+    pub density: f32,
+    pub distance: Option<f32>,
+    pub phase: Phase
+}
+
+impl Fog {
+    pub fn new(density: f32, distance: Option<f32>, phase: Phase) -> Fog {
+        Fog {
+            density,
+            distance,
+            phase
+        }
+    }
+
+    //How far along a ray light scatters, None when it gets through first.
+    //reach is the distance to the next surface, None for rays leaving the scene.
+    pub fn scatter_distance(&self, reach: Option<f32>) -> Option<f32> {
+        let reach = reach.or(self.distance).unwrap_or(f32::INFINITY);
+        let travelled = free_flight(self.density);
+        if travelled < reach { Some(travelled) } else { None }
+    }
+
+    //Fraction of light that makes it distance through the fog, None for light from outside the scene
+    pub fn transmittance(&self, distance: Option<f32>) -> f32 {
+        match distance.or(self.distance) {
+            Some(distance) => (-self.density * distance).exp(),
+            None => 0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Sphere;
+
+    #[test]
+    fn phase_pdf_integrates_to_one() {
+        let record = HitRecord::empty();
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        //Midpoint rule over the whole sphere in cos theta and phi, equal steps cover equal solid angle
+        let (rings, segments) = (2000, 64);
+        let step = 4.0 * PI / (rings * segments) as f32;
+        for g in &[-0.5, 0.0, 0.5] {
+            let phase = Phase::new(Vec3::one(), *g);
+            let mut total = 0.0;
+            for i in 0..rings {
+                let cos_theta = -1.0 + 2.0 * (i as f32 + 0.5) / rings as f32;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                for j in 0..segments {
+                    let phi = (j as f32 + 0.5) / segments as f32 * 2.0 * PI;
+                    let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                    total += phase.pdf(&record, wo, wi) * step;
+                }
+            }
+            assert!((total - 1.0).abs() < 1e-3, "g {} integrates to {}", g, total);
+        }
+    }
+
+    #[test]
+    fn phase_samples_have_mean_cosine_g() {
+        let direction = Vec3::new(0.0, 1.0, 0.0);
+        for g in &[-0.5, 0.0, 0.5] {
+            let phase = Phase::new(Vec3::one(), *g);
+            let count = 100000;
+            let mean = (0..count).map(|_| phase.sample(direction).0.dot(direction)).sum::<f32>() / count as f32;
+            assert!((mean - g).abs() < 0.01, "g {} has mean cosine {}", g, mean);
+        }
+    }
+
+    #[test]
+    fn medium_hits_are_exponentially_distributed() {
+        //The boundary is far enough away that rays almost never get through
+        let boundary : Arc<dyn Hitable + Send> = Arc::new(Sphere::new(Vec3::zero(), 1000.0, 0));
+        let density = 2.0;
+        let medium = ConstantMedium::new(boundary, density, 0);
+        //A ray twice as long per unit t reaches the same depth at half the t
+        let ray = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 2.0));
+
+        let count = 100000;
+        let mut total = 0.0;
+        let mut beyond_mean = 0;
+        for _ in 0..count {
+            let mut record = HitRecord::empty();
+            assert!(medium.hit(&ray, 0.0, f32::MAX, &mut record));
+            let depth = record.t * 2.0;
+            total += depth;
+            if depth > 1.0 / density {
+                beyond_mean += 1;
+            }
+        }
+        let mean = total / count as f32;
+        assert!((mean - 1.0 / density).abs() < 0.01, "mean depth {}", mean);
+        //e^-1 of the hits are deeper than the mean free path
+        let fraction = beyond_mean as f32 / count as f32;
+        assert!((fraction - (-1.0f32).exp()).abs() < 0.01, "{} went past the mean free path", fraction);
+    }
+
+    #[test]
+    fn medium_is_missed_by_rays_that_leave_it_first() {
+        let boundary : Arc<dyn Hitable + Send> = Arc::new(Sphere::new(Vec3::zero(), 1.0, 0));
+        let medium = ConstantMedium::new(boundary, 1e-6, 0);
+        let mut record = HitRecord::empty();
+        assert!(!medium.hit(&Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f32::MAX, &mut record));
+        assert!(!medium.hit(&Ray::new(Vec3::new(0.0, 5.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f32::MAX, &mut record));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::scene::{Hitable, MaterialID, HitRecord, HitableID, aabb_from_corners, to_bvh_ray};
+use crate::math::Ray;
+use crate::glam::Vec3;
+
+use crate::bvh::aabb::{AABB, Bounded};
+use crate::bvh::bounding_hierarchy::BHShape;
+use crate::bvh::bvh::BVH;
+
+use std::f32;
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[(f32, f32); 3]>,
+    pub material_id: MaterialID,
+    node_index: usize
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3; 3], normals: Option<[Vec3; 3]>, uvs: Option<[(f32, f32); 3]>, material_id: MaterialID) -> Triangle {
+        Triangle {
+            vertices,
+            normals,
+            uvs,
+            material_id,
+            node_index: 0
+        }
+    }
+
+    //Moller-Trumbore, returns the distance and the barycentric weights of the second and third vertex
+    #[inline]
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+        let e1 = self.vertices[1] - self.vertices[0];
+        let e2 = self.vertices[2] - self.vertices[0];
+        let p = ray.direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < 1e-9 {
+            return None;
+        }
+
+        let inverse = 1.0 / det;
+        let s = ray.origin - self.vertices[0];
+        let b1 = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let b2 = ray.direction.dot(q) * inverse;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inverse;
+        if t < t_max && t > t_min {
+            Some((t, b1, b2))
+        }
+        else {
+            None
+        }
+    }
+
+    //How position changes with u and v across the triangle, from the uv layout when there is one
+    pub fn derivatives(&self) -> (Vec3, Vec3) {
+        let e1 = self.vertices[1] - self.vertices[0];
+        let e2 = self.vertices[2] - self.vertices[0];
+        let uv = match self.uvs {
+            Some(uv) => uv,
+            None => return (e1, e2)
+        };
+
+        let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+        let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+        let det = du1 * dv2 - dv1 * du2;
+        if det.abs() < 1e-12 {
+            return (e1, e2);
+        }
+        ((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det)
+    }
+}
+
+impl Bounded for Triangle {
+    //Padded so triangles lying flat on an axis plane still have boxes rays can hit
+    fn aabb(&self) -> AABB {
+        let padding = Vec3::one() * 1e-4;
+        let min = self.vertices[0].min(self.vertices[1]).min(self.vertices[2]) - padding;
+        let max = self.vertices[0].max(self.vertices[1]).max(self.vertices[2]) + padding;
+        aabb_from_corners(min, max)
+    }
+}
+
+impl BHShape for Triangle {
+    fn set_bh_node_index(&mut self, index: usize) {
+        self.node_index = index;
+    }
+
+    fn bh_node_index(&self) -> usize {
+        self.node_index
+    }
+}
+
+//A whole triangle mesh as one hitable, with its own BVH so the world BVH only sees one entry per mesh
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    bvh: BVH,
+    min: Vec3,
+    max: Vec3,
+    id: HitableID
+}
+
+impl Mesh {
+    //triangles must not be empty
+    pub fn new(mut triangles: Vec<Triangle>) -> Mesh {
+        let mut min = Vec3::one() * f32::MAX;
+        let mut max = Vec3::one() * f32::MIN;
+        for triangle in &triangles {
+            for vertex in &triangle.vertices {
+                min = min.min(*vertex);
+                max = max.max(*vertex);
+            }
+        }
+
+        let bvh = BVH::build(&mut triangles);
+
+        Mesh {
+            triangles,
+            bvh,
+            min,
+            max,
+            id: 0
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+}
+
+impl Hitable for Mesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+
+        for triangle in self.bvh.traverse(&to_bvh_ray(ray), &self.triangles) {
+            if let Some((t, b1, b2)) = triangle.intersect(ray, t_min, closest_so_far) {
+                closest_so_far = t;
+                closest = Some((triangle, b1, b2));
+            }
+        }
+
+        let (triangle, b1, b2) = match closest {
+            Some(closest) => closest,
+            None => return false
+        };
+        let b0 = 1.0 - b1 - b2;
+
+        record.material = triangle.material_id;
+        record.t = closest_so_far;
+        record.position = ray.point_at_paramater(closest_so_far);
+        record.normal = match triangle.normals {
+            Some(n) => (n[0] * b0 + n[1] * b1 + n[2] * b2).normalize(),
+            None => (triangle.vertices[1] - triangle.vertices[0]).cross(triangle.vertices[2] - triangle.vertices[0]).normalize()
+        };
+        record.hitable = self.id;
+        match triangle.uvs {
+            Some(uv) => {
+                record.u = uv[0].0 * b0 + uv[1].0 * b1 + uv[2].0 * b2;
+                record.v = uv[0].1 * b0 + uv[1].1 * b1 + uv[2].1 * b2;
+            },
+            None => {
+                record.u = b1;
+                record.v = b2;
+            }
+        }
+        let (dpdu, dpdv) = triangle.derivatives();
+        record.set_tangent_frame(dpdu, dpdv);
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        to_bvh_ray(ray).intersects_aabb(&aabb_from_corners(self.min, self.max))
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    fn get_radius(&self) -> f32 {
+        (self.max - self.min).length() * 0.5
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        Some(aabb_from_corners(self.min, self.max))
+    }
+}
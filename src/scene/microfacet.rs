@@ -0,0 +1,269 @@
+use crate::math::*;
+use crate::glam::Vec3;
+use crate::scene::{Material, HitRecord, Hitable, ScatterHit, Texture, ConstantTexture, facing_normal};
+use crate::rand::{thread_rng, Rng};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+//Cook-Torrance with a GGX distribution, parameterized like glTF metallic roughness materials.
+//A Lambertian base sits under the specular layer for dielectrics, metals are all specular tinted by base_color.
+//specular scales the reflectance of dielectrics, 0.5 is the usual 4%.
+pub struct Microfacet {
+    pub base_color: Arc<dyn Texture>,
+    pub roughness: f32,
+    pub metallic: f32,
+    pub specular: f32
+}
+
+impl Microfacet {
+    pub fn new(base_color: Vec3, roughness: f32, metallic: f32, specular: f32) -> Microfacet {
+        Microfacet::textured(Arc::new(ConstantTexture::new(base_color)), roughness, metallic, specular)
+    }
+
+    pub fn textured(base_color: Arc<dyn Texture>, roughness: f32, metallic: f32, specular: f32) -> Microfacet {
+        Microfacet {
+            base_color,
+            roughness: roughness.clamp(0.0, 1.0),
+            metallic: metallic.clamp(0.0, 1.0),
+            specular: specular.max(0.0)
+        }
+    }
+
+    //Squared roughness as is usual, kept off zero so the distribution stays finite
+    #[inline]
+    fn alpha(&self) -> f32 {
+        (self.roughness * self.roughness).max(1e-3)
+    }
+}
+
+#[inline]
+fn schlick_fresnel(f0: Vec3, cosine: f32) -> Vec3 {
+    f0 + (Vec3::one() - f0) * (1.0 - cosine).max(0.0).powi(5)
+}
+
+//Share of microfacets facing along h
+#[inline]
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+//Smith masking for one direction
+#[inline]
+fn smith_g1(n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+//Everything about one shading point that eval, pdf and sampling all need
+struct Lobes {
+    normal: Vec3,
+    diffuse: Vec3,
+    f0: Vec3,
+    alpha: f32,
+    specular_probability: f32
+}
+
+impl Lobes {
+    fn new(material: &Microfacet, record: &HitRecord, wo: Vec3) -> Lobes {
+        let base_color = material.base_color.value(record.u, record.v, record.position);
+        //Opaque, so whichever side the ray came from is the outside
+        let normal = facing_normal(record, wo);
+        let f0 = Vec3::one() * (0.08 * material.specular) * (1.0 - material.metallic) + base_color * material.metallic;
+        let diffuse = base_color * (1.0 - material.metallic);
+
+        //Pick lobes in proportion to roughly how much light each reflects at this angle
+        let specular_weight = luminance(schlick_fresnel(f0, normal.dot(wo)));
+        let diffuse_weight = luminance(diffuse) * (1.0 - specular_weight);
+        let specular_probability = if specular_weight + diffuse_weight > 0.0 {
+            (specular_weight / (specular_weight + diffuse_weight)).clamp(0.1, 1.0)
+        }
+        else {
+            1.0
+        };
+
+        Lobes {
+            normal,
+            diffuse,
+            f0,
+            alpha: material.alpha(),
+            specular_probability
+        }
+    }
+
+    //BRDF value for light arriving along wi and leaving along wo, both pointing away from the surface
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        let n_dot_o = self.normal.dot(wo);
+        let n_dot_i = self.normal.dot(wi);
+        if n_dot_o <= 0.0 || n_dot_i <= 0.0 {
+            return Vec3::zero();
+        }
+
+        let h = (wo + wi).normalize();
+        let fresnel = schlick_fresnel(self.f0, wi.dot(h).max(0.0));
+        let d = ggx_distribution(self.normal.dot(h).max(0.0), self.alpha);
+        let g = smith_g1(n_dot_o, self.alpha) * smith_g1(n_dot_i, self.alpha);
+
+        let specular = fresnel * (d * g / (4.0 * n_dot_o * n_dot_i));
+        let diffuse = (Vec3::one() - fresnel) * self.diffuse / PI;
+        specular + diffuse
+    }
+
+    //Density of sample() producing wi, over solid angle
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let n_dot_i = self.normal.dot(wi);
+        if n_dot_i <= 0.0 {
+            return 0.0;
+        }
+
+        let h = (wo + wi).normalize();
+        let n_dot_h = self.normal.dot(h).max(0.0);
+        let o_dot_h = wo.dot(h);
+        let specular_pdf = if o_dot_h > 0.0 { ggx_distribution(n_dot_h, self.alpha) * n_dot_h / (4.0 * o_dot_h) } else { 0.0 };
+        let diffuse_pdf = n_dot_i / PI;
+        self.specular_probability * specular_pdf + (1.0 - self.specular_probability) * diffuse_pdf
+    }
+
+    //Half vectors are drawn in proportion to D(h) cos(theta_h), diffuse directions by cos(theta)
+    fn sample(&self, wo: Vec3) -> Vec3 {
+        let mut rng = thread_rng();
+        if rng.gen_range(0.0, 1.0) < self.specular_probability {
+            let r1 : f32 = rng.gen_range(0.0, 1.0);
+            let r2 : f32 = rng.gen_range(0.0, 1.0);
+            let phi = 2.0 * PI * r1;
+            let cos_theta = ((1.0 - r2) / (1.0 + (self.alpha * self.alpha - 1.0) * r2)).max(0.0).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let h = to_world(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta), self.normal);
+            reflect(-wo, h)
+        }
+        else {
+            to_world(random_cosine_direction(), self.normal)
+        }
+    }
+}
+
+impl Material for Microfacet {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, _hitable: &dyn Hitable) -> ScatterHit {
+        let wo = -ray_in.get_direction().normalize();
+        let lobes = Lobes::new(self, record, wo);
+        let wi = lobes.sample(wo);
+
+        let pdf = lobes.pdf(wo, wi);
+        let cosine = lobes.normal.dot(wi);
+        if pdf <= 0.0 || cosine <= 0.0 {
+            return ScatterHit::absorbed();
+        }
+
+        //Both lobes could have produced wi, so it is weighted by the combined density
+        ScatterHit::sampled(lobes.eval(wo, wi) * (cosine / pdf), Ray::new(record.position, wi), pdf)
+    }
+
+    fn eval(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        Lobes::new(self, record, wo).eval(wo, wi)
+    }
+
+    fn pdf(&self, record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        Lobes::new(self, record, wo).pdf(wo, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Sphere;
+
+    //A point on the floor seen from wo, the normal points up
+    fn floor() -> HitRecord {
+        let mut record = HitRecord::empty();
+        record.normal = Vec3::new(0.0, 1.0, 0.0);
+        record
+    }
+
+    fn view(degrees: f32) -> Vec3 {
+        let theta = degrees.to_radians();
+        Vec3::new(theta.sin(), theta.cos(), 0.0)
+    }
+
+    //Midpoint rule over the upper hemisphere in cos theta and phi, where equal steps cover equal solid angle
+    fn integrate_hemisphere(f: impl Fn(Vec3) -> f32) -> f32 {
+        let (rings, segments) = (400, 800);
+        let step = 2.0 * PI / (rings * segments) as f32;
+        let mut total = 0.0f64;
+        for i in 0..rings {
+            let cos_theta = (i as f32 + 0.5) / rings as f32;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..segments {
+                let phi = (j as f32 + 0.5) / segments as f32 * 2.0 * PI;
+                total += f(Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin())) as f64;
+            }
+        }
+        total as f32 * step
+    }
+
+    #[test]
+    fn scatter_attenuation_matches_eval_and_pdf() {
+        let record = floor();
+        let sphere = Sphere::new(Vec3::zero(), 1.0, 0);
+        for (roughness, metallic) in &[(0.2, 0.0), (0.5, 1.0), (0.9, 0.3)] {
+            let material = Microfacet::new(Vec3::new(0.9, 0.6, 0.3), *roughness, *metallic, 0.5);
+            let wo = view(40.0);
+            let ray = Ray::new(wo, -wo);
+            for _ in 0..2000 {
+                let hit = material.scatter(&ray, &record, &sphere);
+                if !hit.result {
+                    continue;
+                }
+                let wi = hit.scattered.direction;
+                let pdf = material.pdf(&record, wo, wi);
+                let expected = material.eval(&record, wo, wi) * (wi.y() / pdf);
+                assert!((hit.pdf - pdf).abs() <= 1e-3 * pdf, "scatter pdf {} is not {}", hit.pdf, pdf);
+                assert!((hit.attenuation - expected).length() <= 1e-3 * expected.length().max(1.0), "{:?} is not {:?}", hit.attenuation, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_pdf() {
+        //The reflected fraction estimated from scatter samples has to match the same integral taken over the hemisphere,
+        //which only holds if directions really come out with the density pdf reports
+        let record = floor();
+        let sphere = Sphere::new(Vec3::zero(), 1.0, 0);
+        for (roughness, metallic) in &[(0.3, 0.0), (0.6, 1.0)] {
+            let material = Microfacet::new(Vec3::new(0.8, 0.8, 0.8), *roughness, *metallic, 0.5);
+            let wo = view(30.0);
+            let ray = Ray::new(wo, -wo);
+            let count = 200000;
+            let mut sampled = 0.0f64;
+            for _ in 0..count {
+                let hit = material.scatter(&ray, &record, &sphere);
+                if hit.result {
+                    sampled += luminance(hit.attenuation) as f64;
+                }
+            }
+            let sampled = (sampled / count as f64) as f32;
+            let integrated = integrate_hemisphere(|wi| luminance(material.eval(&record, wo, wi)) * wi.y());
+            assert!((sampled - integrated).abs() < 0.02, "roughness {} metallic {}: sampled {} integrated {}", roughness, metallic, sampled, integrated);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        //Half vectors are sampled without regard to wo, so some reflect under the horizon and scatter drops them.
+        //The density over the hemisphere plus the share of samples lost that way has to make up the whole
+        let record = floor();
+        let sphere = Sphere::new(Vec3::zero(), 1.0, 0);
+        for roughness in &[0.3, 0.6, 1.0] {
+            for metallic in &[0.0, 1.0] {
+                let material = Microfacet::new(Vec3::new(0.5, 0.5, 0.5), *roughness, *metallic, 0.5);
+                let wo = view(20.0);
+                let ray = Ray::new(wo, -wo);
+                let count = 100000;
+                let lost = (0..count).filter(|_| !material.scatter(&ray, &record, &sphere).result).count() as f32 / count as f32;
+                let total = integrate_hemisphere(|wi| material.pdf(&record, wo, wi));
+                assert!((total + lost - 1.0).abs() < 0.01, "roughness {} metallic {}: pdf integrates to {} with {} lost", roughness, metallic, total, lost);
+            }
+        }
+    }
+}
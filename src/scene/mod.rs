@@ -1,11 +1,47 @@
 pub mod material;
+pub mod texture;
+pub mod bump;
+pub mod microfacet;
 pub mod hitable;
 pub mod hitable_list;
 pub mod sphere;
+pub mod moving_sphere;
+pub mod cube;
+pub mod plane;
+pub mod rect;
+pub mod mesh;
+pub mod instance;
+pub mod medium;
+pub mod obj_loader;
 pub mod hitable_library;
+pub mod bounds;
+pub mod environment;
+pub mod sky;
+pub mod light;
+pub mod setup;
+pub mod loader;
+#[cfg(test)]
+pub mod temp_dir;
 
-pub use self::hitable::{HitRecord, Hitable, RayCastResult, HitableID};
-pub use self::material::{Material, MaterialID, MaterialLibrary, Lambertian, Metal, Deilectric, ScatterHit, Sky, CheckerBoard};
+pub use self::hitable::{HitRecord, Hitable, HitableID, flat_light_pdf};
+pub use self::texture::{Texture, ConstantTexture, CheckerTexture, CheckerSpace, checker_is_even, ImageTexture, WrapMode, NoiseTexture, NoiseStyle};
+pub use self::bump::{BumpMapped, SurfaceDetail};
+pub use self::microfacet::Microfacet;
+pub use self::material::{Material, MaterialID, MaterialLibrary, Lambertian, Metal, Deilectric, ScatterHit, CheckerBoard, DiffuseLight, facing_normal};
 pub use self::hitable_list::HitableList;
 pub use self::hitable_library::HitableLibrary;
-pub use self::sphere::Sphere;
\ No newline at end of file
+pub use self::sphere::{Sphere, sphere_uv, sphere_derivatives, sphere_cone_pdf, sphere_cone_direction};
+pub use self::moving_sphere::MovingSphere;
+pub use self::cube::Cube;
+pub use self::plane::{Plane, Disk};
+pub use self::rect::{XYRect, XZRect, YZRect};
+pub use self::mesh::{Mesh, Triangle};
+pub use self::obj_loader::load_obj;
+pub use self::instance::Instance;
+pub use self::medium::{ConstantMedium, Phase, Fog};
+pub use self::bounds::{HitableBounds, to_point, from_point, aabb_from_corners, to_bvh_ray};
+pub use self::environment::{Environment, EnvironmentMap};
+pub use self::sky::Daylight;
+pub use self::light::Light;
+pub use self::setup::Scene;
+pub use self::loader::{load_scene, parse_scene, SceneError};
\ No newline at end of file
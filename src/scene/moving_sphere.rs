@@ -0,0 +1,121 @@
+use crate::scene::{Hitable, MaterialID, HitRecord, HitableID, aabb_from_corners, sphere_uv, sphere_derivatives, sphere_cone_pdf, sphere_cone_direction};
+use crate::math::Ray;
+use crate::glam::Vec3;
+use crate::bvh::aabb::AABB;
+
+//Sphere moving in a straight line from center0 at time0 to center1 at time1, it rests at the ends outside that range
+pub struct MovingSphere {
+    center0: Vec3,
+    center1: Vec3,
+    time0: f32,
+    time1: f32,
+    radius: f32,
+    radius_sqrd: f32,
+    material_id: MaterialID,
+    id: HitableID
+}
+
+impl MovingSphere {
+    pub fn new(center0: Vec3, center1: Vec3, time0: f32, time1: f32, radius: f32, material_id: MaterialID) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            radius_sqrd: radius * radius,
+            material_id,
+            id: 0
+        }
+    }
+
+    #[inline]
+    pub fn center(&self, time: f32) -> Vec3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let s = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + (self.center1 - self.center0) * s
+    }
+}
+
+impl Hitable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let center = self.center(ray.time);
+        let oc = ray.get_origin() - center;
+        let a = ray.direction.dot(ray.direction);
+        let b = oc.dot(ray.direction);
+        let c = oc.dot(oc) - self.radius_sqrd;
+        let d = b * b - a * c;
+        if d <= 0.0 {
+            return false;
+        }
+
+        let d = d.sqrt();
+        let mut t = (-b - d) / a;
+        if t >= t_max || t <= t_min {
+            t = (-b + d) / a;
+            if t >= t_max || t <= t_min {
+                return false;
+            }
+        }
+
+        record.material = self.material_id;
+        record.t = t;
+        record.position = ray.point_at_paramater(t);
+        record.normal = (record.position - center) / self.radius;
+        record.hitable = self.id;
+        let direction = (record.position - center) / self.radius.abs();
+        let (u, v) = sphere_uv(direction);
+        record.u = u;
+        record.v = v;
+        let (dpdu, dpdv) = sphere_derivatives(direction, self.radius.abs());
+        record.set_tangent_frame(dpdu, dpdv);
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        let oc = ray.get_origin() - self.center(ray.time);
+        let a = ray.direction.dot(ray.direction);
+        let b = oc.dot(ray.direction);
+        let c = oc.dot(oc) - self.radius_sqrd;
+        b * b - a * c > 0.0
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        (self.center0 + self.center1) * 0.5
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius.abs()
+    }
+
+    //Covers the whole path so the BVH finds it at any time
+    fn bounding_box(&self) -> Option<AABB> {
+        let extent = Vec3::one() * self.radius.abs();
+        let min = self.center0.min(self.center1) - extent;
+        let max = self.center0.max(self.center1) + extent;
+        Some(aabb_from_corners(min, max))
+    }
+
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        let mut record = HitRecord::empty();
+        if !self.hit(&Ray::new_at_time(origin, direction, time), 0.001, f32::MAX, &mut record) {
+            return 0.0;
+        }
+        sphere_cone_pdf(self.center(time), self.radius.abs(), origin)
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        sphere_cone_direction(self.center(time), self.radius.abs(), origin)
+    }
+}
@@ -0,0 +1,415 @@
+use crate::scene::*;
+use crate::glam::Vec3;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/*
+Reads Wavefront OBJ files into a single Mesh.
+Supports v, vt, vn, f (polygons are fanned into triangles, negative indices count back from the end),
+usemtl and mtllib, everything else is skipped.
+
+Kd can come from an image with map_Kd, which then shows up on diffuse materials.
+Pr and Pm from the PBR extension turn a material into a Microfacet.
+norm gives any material a tangent space normal map and map_Bump or bump a height map.
+Each usemtl name is looked up in the materials the scene already declared first, so a scene can swap out
+what the model asked for, then in the .mtl files the OBJ pulled in, and a name found in neither is an error.
+Faces before the first usemtl use default_material.
+*/
+
+pub fn load_obj(path: &Path, material_library: &mut MaterialLibrary, scene_materials: &HashMap<String, MaterialID>, default_material: Option<MaterialID>) -> Result<Mesh, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let error = |line: usize, message: String| format!("{} line {}: {}", path.display(), line, message);
+
+    let mut positions : Vec<Vec3> = vec![];
+    let mut normals : Vec<Vec3> = vec![];
+    let mut uvs : Vec<(f32, f32)> = vec![];
+    let mut triangles = vec![];
+
+    let mut mtl_materials : HashMap<String, MaterialID> = HashMap::new();
+    let mut current_material = default_material;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = match text.find('#') {
+            Some(comment) => &text[..comment],
+            None => text
+        };
+        let mut tokens = text.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let rest : Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&rest).map_err(|e| error(line, e))?),
+            "vn" => normals.push(parse_vec3(&rest).map_err(|e| error(line, e))?),
+            "vt" => {
+                let u = rest.first().and_then(|u| u.parse::<f32>().ok());
+                let v = rest.get(1).and_then(|v| v.parse::<f32>().ok()).or(Some(0.0));
+                match (u, v) {
+                    (Some(u), Some(v)) => uvs.push((u, v)),
+                    _ => return Err(error(line, String::from("vt expects u and v")))
+                }
+            },
+            "f" => {
+                let material = match current_material {
+                    Some(material) => material,
+                    None => return Err(error(line, String::from("face has no material, give the mesh a default material or use usemtl")))
+                };
+
+                let mut corners = vec![];
+                for corner in &rest {
+                    corners.push(parse_corner(corner, positions.len(), uvs.len(), normals.len()).map_err(|e| error(line, e))?);
+                }
+                if corners.len() < 3 {
+                    return Err(error(line, String::from("a face needs at least three vertices")));
+                }
+
+                for i in 1..corners.len() - 1 {
+                    let face = [corners[0], corners[i], corners[i + 1]];
+                    let vertices = [positions[face[0].0], positions[face[1].0], positions[face[2].0]];
+                    let face_uvs = match (face[0].1, face[1].1, face[2].1) {
+                        (Some(a), Some(b), Some(c)) => Some([uvs[a], uvs[b], uvs[c]]),
+                        _ => None
+                    };
+                    let face_normals = match (face[0].2, face[1].2, face[2].2) {
+                        (Some(a), Some(b), Some(c)) => Some([normals[a], normals[b], normals[c]]),
+                        _ => None
+                    };
+                    triangles.push(Triangle::new(vertices, face_normals, face_uvs, material));
+                }
+            },
+            "mtllib" => {
+                for file in &rest {
+                    load_mtl(&base_dir.join(file), material_library, &mut mtl_materials)?;
+                }
+            },
+            "usemtl" => {
+                let name = rest.join(" ");
+                current_material = match scene_materials.get(&name).or_else(|| mtl_materials.get(&name)) {
+                    Some(id) => Some(*id),
+                    None => return Err(error(line, format!("unknown material '{}'", name)))
+                };
+            },
+            _ => {}
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err(format!("{}: no faces", path.display()));
+    }
+
+    Ok(Mesh::new(triangles))
+}
+
+fn parse_vec3(values: &[&str]) -> Result<Vec3, String> {
+    let parsed : Vec<Option<f32>> = values.iter().take(3).map(|v| v.parse::<f32>().ok()).collect();
+    match parsed.as_slice() {
+        [Some(x), Some(y), Some(z)] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err(String::from("expected three numbers"))
+    }
+}
+
+//OBJ indices start at 1, negative ones count back from the last element read so far
+fn parse_index(value: &str, count: usize) -> Result<usize, String> {
+    let index = value.parse::<i64>().map_err(|_| format!("bad index '{}'", value))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} is out of range", index));
+    }
+    Ok(resolved as usize)
+}
+
+//v, v/vt, v//vn or v/vt/vn
+fn parse_corner(corner: &str, positions: usize, uvs: usize, normals: usize) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = corner.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), positions)?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(parse_index(uv, uvs)?),
+        _ => None
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(parse_index(normal, normals)?),
+        _ => None
+    };
+    Ok((position, uv, normal))
+}
+
+struct MtlDefinition {
+    diffuse: Vec3,
+    //Mirrors fall back to the diffuse color when Ks is missing
+    specular: Option<Vec3>,
+    emissive: Vec3,
+    shininess: f32,
+    //From the PBR extension, when either is given the material becomes a Microfacet
+    roughness: Option<f32>,
+    metallic: Option<f32>,
+    ior: f32,
+    dissolve: f32,
+    illum: u32,
+    diffuse_map: Option<PathBuf>,
+    normal_map: Option<PathBuf>,
+    bump_map: Option<PathBuf>
+}
+
+impl MtlDefinition {
+    fn new() -> MtlDefinition {
+        MtlDefinition {
+            diffuse: Vec3::one() * 0.8,
+            specular: None,
+            emissive: Vec3::zero(),
+            shininess: 0.0,
+            roughness: None,
+            metallic: None,
+            ior: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+            normal_map: None,
+            bump_map: None
+        }
+    }
+
+    //Picks the closest of our materials, there is no general way to map the Phong style parameters
+    fn build(&self) -> Result<Box<dyn Material + Send>, String> {
+        let diffuse : Arc<dyn Texture> = match &self.diffuse_map {
+            Some(path) => Arc::new(ImageTexture::load(path, WrapMode::Repeat)?),
+            None => Arc::new(ConstantTexture::new(self.diffuse))
+        };
+
+        let material : Box<dyn Material + Send> = if self.emissive.length() > 0.0 {
+            Box::new(DiffuseLight::new(self.emissive))
+        }
+        else if self.roughness.is_some() || self.metallic.is_some() {
+            Box::new(Microfacet::textured(diffuse, self.roughness.unwrap_or(0.5), self.metallic.unwrap_or(0.0), 0.5))
+        }
+        else if self.illum == 7 || self.dissolve < 1.0 {
+            Box::new(Deilectric::new(self.ior.max(1.0)))
+        }
+        else if self.illum == 3 || self.illum == 5 {
+            //Ns runs roughly 0 to 1000, a shinier surface gets less fuzz
+            let fuzz = (1.0 - self.shininess / 1000.0).max(0.0).powf(4.0);
+            match self.specular {
+                Some(specular) => Box::new(Metal::new(specular, fuzz)),
+                None => Box::new(Metal::textured(diffuse, fuzz))
+            }
+        }
+        else {
+            Box::new(Lambertian::textured(diffuse))
+        };
+
+        let detail = if let Some(path) = &self.normal_map {
+            Some(SurfaceDetail::NormalMap { texture: Arc::new(ImageTexture::load(path, WrapMode::Repeat)?), strength: 1.0 })
+        }
+        else if let Some(path) = &self.bump_map {
+            Some(SurfaceDetail::Bump { height: Arc::new(ImageTexture::load(path, WrapMode::Repeat)?), scale: 0.05 })
+        }
+        else {
+            None
+        };
+
+        match detail {
+            Some(detail) => Ok(Box::new(BumpMapped::new(material, detail))),
+            None => Ok(material)
+        }
+    }
+}
+
+fn load_mtl(path: &Path, material_library: &mut MaterialLibrary, materials: &mut HashMap<String, MaterialID>) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let error = |line: usize, message: String| format!("{} line {}: {}", path.display(), line, message);
+
+    let mut current : Option<(String, MtlDefinition)> = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut tokens = text.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let rest : Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, definition)) = current.take() {
+                materials.insert(name, material_library.add_new(definition.build()?));
+            }
+            current = Some((rest.join(" "), MtlDefinition::new()));
+            continue;
+        }
+
+        let definition = match current.as_mut() {
+            Some((_, definition)) => definition,
+            None => continue
+        };
+        let number = |values: &[&str]| values.first().and_then(|v| v.parse::<f32>().ok()).ok_or_else(|| error(line, format!("{} expects a number", keyword)));
+
+        match keyword {
+            "Kd" => definition.diffuse = parse_vec3(&rest).map_err(|e| error(line, e))?,
+            "Ks" => definition.specular = Some(parse_vec3(&rest).map_err(|e| error(line, e))?),
+            "Ke" => definition.emissive = parse_vec3(&rest).map_err(|e| error(line, e))?,
+            "Ns" => definition.shininess = number(&rest)?,
+            "Pr" => definition.roughness = Some(number(&rest)?),
+            "Pm" => definition.metallic = Some(number(&rest)?),
+            "Ni" => definition.ior = number(&rest)?,
+            "d" => definition.dissolve = number(&rest)?,
+            "Tr" => definition.dissolve = 1.0 - number(&rest)?,
+            "illum" => definition.illum = number(&rest)? as u32,
+            //Options like -s or -o before the file name are not supported, the last token is taken as the file
+            "map_Kd" | "norm" | "map_Bump" | "bump" => {
+                let file = match rest.last() {
+                    Some(file) => base_dir.join(file),
+                    None => return Err(error(line, format!("{} expects a file", keyword)))
+                };
+                match keyword {
+                    "map_Kd" => definition.diffuse_map = Some(file),
+                    "norm" => definition.normal_map = Some(file),
+                    _ => definition.bump_map = Some(file)
+                }
+            },
+            _ => {}
+        }
+    }
+
+    if let Some((name, definition)) = current.take() {
+        materials.insert(name, material_library.add_new(definition.build()?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{HitRecord, Hitable, Lambertian, Sphere};
+    use crate::scene::temp_dir::TempDir;
+    use crate::math::Ray;
+
+    //Keep the returned directory alive until the files have been read
+    fn write_files(test: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(&format!("obj_loader_{}", test));
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn material_at(mesh: &Mesh, x: f32, y: f32) -> Option<MaterialID> {
+        let mut record = HitRecord::empty();
+        let ray = Ray::new(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        if mesh.hit(&ray, 0.001, f32::MAX, &mut record) { Some(record.material) } else { None }
+    }
+
+    fn load_error(test: &str, obj: &str, default_material: Option<MaterialID>) -> String {
+        let dir = write_files(test, &[("model.obj", obj)]);
+        let path = dir.join("model.obj");
+        match load_obj(&path, &mut MaterialLibrary::new(), &HashMap::new(), default_material) {
+            Ok(_) => panic!("model loaded without an error"),
+            Err(error) => error.replace(&format!("{}", path.display()), "model.obj")
+        }
+    }
+
+    #[test]
+    fn loads_faces_and_mtl_materials() {
+        let obj = "
+mtllib model.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 2 0 0
+v 3 0 0
+v 2 1 0
+vn 0 0 1
+# a quad split into two triangles, then a lone triangle with relative indices
+usemtl red
+f 1//1 2//1 3//1 4//1
+usemtl shared
+f -3 -2 -1
+";
+        let mtl = "
+newmtl red
+Kd 0.8 0.1 0.1
+Ns 10
+newmtl unused
+Kd 0.1 0.1 0.8
+";
+        let dir = write_files("valid", &[("model.obj", obj), ("model.mtl", mtl)]);
+        let mut library = MaterialLibrary::new();
+        let default_material = library.add_new(Box::new(Lambertian::new(Vec3::one())));
+        let shared = library.add_new(Box::new(Lambertian::new(Vec3::one())));
+        let mut scene_materials = HashMap::new();
+        scene_materials.insert(String::from("shared"), shared);
+
+        let mesh = match load_obj(&dir.join("model.obj"), &mut library, &scene_materials, Some(default_material)) {
+            Ok(mesh) => mesh,
+            Err(error) => panic!("{}", error)
+        };
+        assert_eq!(mesh.triangle_count(), 3);
+
+        let red = material_at(&mesh, 0.5, 0.5).unwrap();
+        assert_ne!(red, default_material);
+        assert_ne!(red, shared);
+        assert_eq!(material_at(&mesh, 2.2, 0.2), Some(shared));
+        assert_eq!(material_at(&mesh, 5.0, 5.0), None);
+    }
+
+    #[test]
+    fn reports_out_of_range_indices() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 1 2 4\n";
+        assert_eq!(load_error("range", obj, Some(0)), "model.obj line 5: index 4 is out of range");
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 2 3\n";
+        assert_eq!(load_error("negative", obj, Some(0)), "model.obj line 4: index -4 is out of range");
+    }
+
+    #[test]
+    fn reports_bad_vertices() {
+        assert_eq!(load_error("vertex", "v 0 0 0\nv 1 x 0\n", Some(0)), "model.obj line 2: expected three numbers");
+    }
+
+    #[test]
+    fn reports_faces_without_a_material() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        assert_eq!(load_error("material", obj, None), "model.obj line 4: face has no material, give the mesh a default material or use usemtl");
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n";
+        assert_eq!(load_error("usemtl", obj, None), "model.obj line 4: unknown material 'missing'");
+        //A default material only covers faces before any usemtl, it does not hide a misspelled name
+        assert_eq!(load_error("usemtl_default", obj, Some(0)), "model.obj line 4: unknown material 'missing'");
+    }
+
+    #[test]
+    fn reports_bad_mtl_numbers() {
+        let dir = write_files("mtl", &[("model.obj", "mtllib model.mtl\n"), ("model.mtl", "newmtl a\nKd 1 1 1\nNs shiny\n")]);
+        let error = match load_obj(&dir.join("model.obj"), &mut MaterialLibrary::new(), &HashMap::new(), None) {
+            Ok(_) => panic!("model loaded without an error"),
+            Err(error) => error
+        };
+        assert!(error.ends_with("model.mtl line 3: Ns expects a number"), "{}", error);
+    }
+
+    //Color a mirror built from definition reflects straight down onto it
+    fn mirror_color(definition: &MtlDefinition) -> Vec3 {
+        let material = definition.build().unwrap();
+        let mut record = HitRecord::empty();
+        record.normal = Vec3::new(0.0, 1.0, 0.0);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        material.scatter(&ray, &record, &Sphere::new(Vec3::zero(), 1.0, 0)).attenuation
+    }
+
+    #[test]
+    fn mirrors_without_ks_reflect_kd() {
+        let mut definition = MtlDefinition::new();
+        definition.illum = 3;
+        definition.shininess = 1000.0;
+        definition.diffuse = Vec3::new(0.8, 0.2, 0.1);
+        assert_eq!(mirror_color(&definition), Vec3::new(0.8, 0.2, 0.1));
+
+        definition.specular = Some(Vec3::new(0.9, 0.9, 0.9));
+        assert_eq!(mirror_color(&definition), Vec3::new(0.9, 0.9, 0.9));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::scene::{Hitable, MaterialID, HitRecord, HitableID, aabb_from_corners, flat_light_pdf};
+use crate::math::{Ray, orthonormal_basis, random_in_unit_disk};
+use crate::glam::Vec3;
+use crate::bvh::aabb::AABB;
+
+use std::f32;
+
+//Both shapes are flat and two sided, the normal in the hit record always faces back along the ray
+
+#[inline]
+fn plane_distance(ray: &Ray, point: Vec3, normal: Vec3) -> Option<f32> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+    Some((point - ray.origin).dot(normal) / denominator)
+}
+
+#[inline]
+fn facing(normal: Vec3, ray: &Ray) -> Vec3 {
+    if normal.dot(ray.direction) > 0.0 { -normal } else { normal }
+}
+
+//Infinite plane through point, uv are distances along the plane in world units
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material_id: MaterialID,
+    id: HitableID
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material_id: MaterialID) -> Plane {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material_id,
+            id: 0
+        }
+    }
+}
+
+impl Hitable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let t = match plane_distance(ray, self.point, self.normal) {
+            Some(t) if t < t_max && t > t_min => t,
+            _ => return false
+        };
+
+        record.material = self.material_id;
+        record.t = t;
+        record.position = ray.point_at_paramater(t);
+        record.normal = facing(self.normal, ray);
+        record.hitable = self.id;
+        let local = record.position - self.point;
+        record.u = local.dot(self.tangent);
+        record.v = local.dot(self.bitangent);
+        record.set_tangent_frame(self.tangent, self.bitangent);
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        plane_distance(ray, self.point, self.normal).is_some_and(|t| t > 0.0)
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        self.point
+    }
+
+    fn get_radius(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    //Unbounded, the world checks it against every ray instead of putting it in the BVH
+    fn bounding_box(&self) -> Option<AABB> {
+        None
+    }
+
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+}
+
+//Flat disk facing along normal, uv map the disk onto the unit square
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material_id: MaterialID,
+    id: HitableID
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material_id: MaterialID) -> Disk {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Disk {
+            center,
+            normal,
+            radius: radius.abs(),
+            tangent,
+            bitangent,
+            material_id,
+            id: 0
+        }
+    }
+
+    #[inline]
+    fn distance(&self, ray: &Ray) -> Option<f32> {
+        let t = plane_distance(ray, self.center, self.normal)?;
+        let offset = ray.point_at_paramater(t) - self.center;
+        if offset.dot(offset) > self.radius * self.radius {
+            return None;
+        }
+        Some(t)
+    }
+}
+
+impl Hitable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let t = match self.distance(ray) {
+            Some(t) if t < t_max && t > t_min => t,
+            _ => return false
+        };
+
+        record.material = self.material_id;
+        record.t = t;
+        record.position = ray.point_at_paramater(t);
+        record.normal = facing(self.normal, ray);
+        record.hitable = self.id;
+        let local = (record.position - self.center) / self.radius;
+        record.u = (local.dot(self.tangent) + 1.0) * 0.5;
+        record.v = (local.dot(self.bitangent) + 1.0) * 0.5;
+        record.set_tangent_frame(self.tangent, self.bitangent);
+        true
+    }
+
+    #[inline]
+    fn quick_hit(&self, ray: &Ray) -> bool {
+        self.distance(ray).is_some_and(|t| t > 0.0)
+    }
+
+    fn set_hitable_id(&mut self, id: HitableID) {
+        self.id = id;
+    }
+
+    fn get_center(&self) -> Vec3 {
+        self.center
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    //Only as thick as it has to be along each axis, so disks facing down an axis stay flat
+    fn bounding_box(&self) -> Option<AABB> {
+        let extent = |axis: Vec3| {
+            let cosine = self.normal.dot(axis);
+            self.radius * (1.0 - cosine * cosine).max(0.0).sqrt() + 1e-4
+        };
+        let extents = Vec3::new(
+            extent(Vec3::new(1.0, 0.0, 0.0)),
+            extent(Vec3::new(0.0, 1.0, 0.0)),
+            extent(Vec3::new(0.0, 0.0, 1.0))
+        );
+        Some(aabb_from_corners(self.center - extents, self.center + extents))
+    }
+
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        flat_light_pdf(self, f32::consts::PI * self.radius * self.radius, origin, direction, time)
+    }
+
+    fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+        let p = random_in_unit_disk() * self.radius;
+        self.center + self.tangent * p.x() + self.bitangent * p.y() - origin
+    }
+}
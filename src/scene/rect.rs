@@ -0,0 +1,122 @@
+use crate::scene::{Hitable, MaterialID, HitRecord, HitableID, aabb_from_corners, flat_light_pdf};
+use crate::rand::{thread_rng, Rng};
+use crate::math::Ray;
+use crate::glam::Vec3;
+use crate::bvh::aabb::AABB;
+
+//Axis aligned rectangles spanning [a0, a1] x [b0, b1] on the plane where the remaining axis equals k.
+//They are two sided, the normal faces back along the ray, and uv run 0 to 1 across the two spanned axes.
+macro_rules! axis_rect {
+    ($name:ident, $a:ident, $b:ident, $k:ident, $set_a:ident, $set_b:ident, $set_k:ident) => {
+        pub struct $name {
+            a0: f32,
+            a1: f32,
+            b0: f32,
+            b1: f32,
+            k: f32,
+            material_id: MaterialID,
+            id: HitableID
+        }
+
+        impl $name {
+            pub fn new(a0: f32, a1: f32, b0: f32, b1: f32, k: f32, material_id: MaterialID) -> $name {
+                $name {
+                    a0: a0.min(a1),
+                    a1: a0.max(a1),
+                    b0: b0.min(b1),
+                    b1: b0.max(b1),
+                    k,
+                    material_id,
+                    id: 0
+                }
+            }
+
+            #[inline]
+            fn point(a: f32, b: f32, k: f32) -> Vec3 {
+                let mut point = Vec3::zero();
+                point.$set_a(a);
+                point.$set_b(b);
+                point.$set_k(k);
+                point
+            }
+
+            //Distance along the ray and the spanned coordinates where it crosses the rectangle
+            #[inline]
+            fn distance(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
+                if ray.direction.$k() == 0.0 {
+                    return None;
+                }
+                let t = (self.k - ray.origin.$k()) / ray.direction.$k();
+                let a = ray.origin.$a() + t * ray.direction.$a();
+                let b = ray.origin.$b() + t * ray.direction.$b();
+                if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
+                    return None;
+                }
+                Some((t, a, b))
+            }
+        }
+
+        impl Hitable for $name {
+            fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+                let (t, a, b) = match self.distance(ray) {
+                    Some((t, a, b)) if t < t_max && t > t_min => (t, a, b),
+                    _ => return false
+                };
+
+                record.material = self.material_id;
+                record.t = t;
+                record.position = ray.point_at_paramater(t);
+                record.normal = $name::point(0.0, 0.0, if ray.direction.$k() > 0.0 { -1.0 } else { 1.0 });
+                record.hitable = self.id;
+                record.u = (a - self.a0) / (self.a1 - self.a0);
+                record.v = (b - self.b0) / (self.b1 - self.b0);
+                record.set_tangent_frame($name::point(self.a1 - self.a0, 0.0, 0.0), $name::point(0.0, self.b1 - self.b0, 0.0));
+                true
+            }
+
+            #[inline]
+            fn quick_hit(&self, ray: &Ray) -> bool {
+                self.distance(ray).is_some_and(|(t, _, _)| t > 0.0)
+            }
+
+            fn set_hitable_id(&mut self, id: HitableID) {
+                self.id = id;
+            }
+
+            fn get_center(&self) -> Vec3 {
+                $name::point((self.a0 + self.a1) * 0.5, (self.b0 + self.b1) * 0.5, self.k)
+            }
+
+            fn get_radius(&self) -> f32 {
+                (self.a1 - self.a0).hypot(self.b1 - self.b0) * 0.5
+            }
+
+            //Padded along the flat axis so the box has some thickness for the BVH
+            fn bounding_box(&self) -> Option<AABB> {
+                Some(aabb_from_corners(
+                    $name::point(self.a0, self.b0, self.k - 1e-4),
+                    $name::point(self.a1, self.b1, self.k + 1e-4)
+                ))
+            }
+
+            fn material_id(&self) -> Option<MaterialID> {
+                Some(self.material_id)
+            }
+
+            fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+                flat_light_pdf(self, (self.a1 - self.a0) * (self.b1 - self.b0), origin, direction, time)
+            }
+
+            fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+                let mut rng = thread_rng();
+                let a = if self.a1 > self.a0 { rng.gen_range(self.a0, self.a1) } else { self.a0 };
+                let b = if self.b1 > self.b0 { rng.gen_range(self.b0, self.b1) } else { self.b0 };
+                $name::point(a, b, self.k) - origin
+            }
+        }
+    };
+}
+
+axis_rect!(XYRect, x, y, z, set_x, set_y, set_z);
+axis_rect!(XZRect, x, z, y, set_x, set_z, set_y);
+axis_rect!(YZRect, y, z, x, set_y, set_z, set_x);
@@ -0,0 +1,39 @@
+use crate::scene::{MaterialLibrary, HitableLibrary, HitableList, HitableID, Environment, Light, Fog};
+use crate::controls::CameraSettings;
+
+//Everything a frame needs besides the render settings
+pub struct Scene {
+    pub material_library: MaterialLibrary,
+    pub hitable_library: HitableLibrary,
+    pub world: HitableList,
+    pub camera: CameraSettings,
+    pub environment: Environment,
+    //Objects in the world with an emissive material, the integrator aims shadow rays at these
+    pub emitters: Vec<HitableID>,
+    //Point, spot and directional lights, these have no shape in the world
+    pub lights: Vec<Light>,
+    //Haze filling the space between surfaces, None for clear air
+    pub fog: Option<Fog>
+}
+
+impl Scene {
+    pub fn new(material_library: MaterialLibrary, hitable_library: HitableLibrary, world: HitableList, camera: CameraSettings, environment: Environment) -> Scene {
+        let emitters = world.ids().iter().cloned().filter(|id| {
+            hitable_library.checkout_hitable(*id)
+                .and_then(|object| object.material_id())
+                .and_then(|material| material_library.checkout_material(material))
+                .is_some_and(|material| material.is_emissive())
+        }).collect();
+
+        Scene {
+            material_library,
+            hitable_library,
+            world,
+            camera,
+            environment,
+            emitters,
+            lights: vec![],
+            fog: None
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::glam::Vec3;
+use crate::scene::{sphere_cone_pdf, sphere_cone_direction};
+
+use std::f32::consts::PI;
+
+//Kilocandela per square meter, the unit of the sky model, to render units.
+//Keeps a clear midday sky inside display range without any tone mapping.
+const SKY_SCALE: f32 = 0.05;
+//Kilolux of sunlight arriving at the top of the atmosphere
+const SUN_ILLUMINANCE: f32 = 128.0;
+
+//Perez et al. luminance distribution, the shape both the brightness and the chromaticity of the sky follow
+#[derive(Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32
+}
+
+impl Perez {
+    #[inline]
+    fn value(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta).exp()) * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+#[inline]
+fn polynomial(coefficients: [f32; 4], theta: f32) -> f32 {
+    ((coefficients[0] * theta + coefficients[1]) * theta + coefficients[2]) * theta + coefficients[3]
+}
+
+//Rayleigh and aerosol attenuation of sunlight along the path through the air, per red, green and blue wavelength.
+//From the appendix of the Preetham paper, ozone and water vapour are left out.
+fn sun_transmittance(theta: f32, turbidity: f32) -> Vec3 {
+    let degrees = theta.to_degrees();
+    let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - degrees).max(1e-3).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let channel = |wavelength: f32| {
+        let rayleigh = (-0.008735 * wavelength.powf(-4.08) * air_mass).exp();
+        let aerosol = (-beta * wavelength.powf(-1.3) * air_mass).exp();
+        rayleigh * aerosol
+    };
+    Vec3::new(channel(0.680), channel(0.550), channel(0.440))
+}
+
+#[inline]
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    if y <= 0.0 {
+        return Vec3::zero();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Vec3::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z
+    ).max(Vec3::zero())
+}
+
+//Preetham, Shirley and Smits analytic daylight for a sun above the horizon, with the sun itself as a small disk
+//so it lights the scene with soft shadows and shows up in the camera and in reflections.
+//The model only covers the sky, everything below the horizon is black.
+pub struct Daylight {
+    //Toward the sun, normalized
+    pub sun_direction: Vec3,
+    pub turbidity: f32,
+    luminance: Perez,
+    chroma_x: Perez,
+    chroma_y: Perez,
+    //Sky value at the zenith in xyY divided by the Perez distribution there, every other direction scales this
+    zenith: (f32, f32, f32),
+    //Radius of the sun disk as the sine of its angle, used to sample it like a sphere one unit away
+    sun_radius: f32,
+    cos_sun_radius: f32,
+    sun_radiance: Vec3,
+    intensity: f32
+}
+
+impl Daylight {
+    //sun_direction must point above the horizon, sun_size is the angular diameter of the disk in degrees and 0 leaves the sun out
+    pub fn new(sun_direction: Vec3, turbidity: f32, sun_size: f32, intensity: f32) -> Daylight {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity;
+        let theta_sun = sun_direction.y().clamp(0.0, 1.0).acos();
+
+        let luminance = Perez { a: 0.1787 * t - 1.4630, b: -0.3554 * t + 0.4275, c: -0.0227 * t + 5.3251, d: 0.1206 * t - 2.5771, e: -0.0670 * t + 0.3703 };
+        let chroma_x = Perez { a: -0.0193 * t - 0.2592, b: -0.0665 * t + 0.0008, c: -0.0004 * t + 0.2125, d: -0.0641 * t - 0.8989, e: -0.0033 * t + 0.0452 };
+        let chroma_y = Perez { a: -0.0167 * t - 0.2608, b: -0.0950 * t + 0.0092, c: -0.0079 * t + 0.2102, d: -0.0441 * t - 1.6537, e: -0.0109 * t + 0.0529 };
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = t * t * polynomial([0.00166, -0.00375, 0.00209, 0.0], theta_sun)
+            + t * polynomial([-0.02903, 0.06377, -0.03202, 0.00394], theta_sun)
+            + polynomial([0.11693, -0.21196, 0.06052, 0.25886], theta_sun);
+        let zenith_y = t * t * polynomial([0.00275, -0.00610, 0.00317, 0.0], theta_sun)
+            + t * polynomial([-0.04214, 0.08970, -0.04153, 0.00516], theta_sun)
+            + polynomial([0.15346, -0.26756, 0.06670, 0.26688], theta_sun);
+
+        let zenith = (
+            zenith_x / chroma_x.value(1.0, theta_sun),
+            zenith_y / chroma_y.value(1.0, theta_sun),
+            zenith_luminance.max(0.0) / luminance.value(1.0, theta_sun)
+        );
+
+        let angle = (sun_size * 0.5).to_radians();
+        let solid_angle = 2.0 * PI * (1.0 - angle.cos());
+        let sun_radiance = if sun_size > 0.0 {
+            sun_transmittance(theta_sun, t) * (SUN_ILLUMINANCE * SKY_SCALE / solid_angle)
+        }
+        else {
+            Vec3::zero()
+        };
+
+        Daylight {
+            sun_direction,
+            turbidity,
+            luminance,
+            chroma_x,
+            chroma_y,
+            zenith,
+            sun_radius: angle.sin(),
+            cos_sun_radius: angle.cos(),
+            sun_radiance,
+            intensity
+        }
+    }
+
+    //The sky alone, without the sun disk
+    pub fn sky_color(&self, direction: Vec3) -> Vec3 {
+        if direction.y() <= 0.0 {
+            return Vec3::zero();
+        }
+        let cos_theta = direction.y().max(1e-3);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let x = self.zenith.0 * self.chroma_x.value(cos_theta, gamma);
+        let y = self.zenith.1 * self.chroma_y.value(cos_theta, gamma);
+        let luminance = self.zenith.2 * self.luminance.value(cos_theta, gamma);
+        xyy_to_rgb(x, y, luminance * SKY_SCALE) * self.intensity
+    }
+
+    #[inline]
+    fn has_sun(&self) -> bool {
+        self.sun_radiance.x() > 0.0 || self.sun_radiance.y() > 0.0 || self.sun_radiance.z() > 0.0
+    }
+
+    pub fn color(&self, direction: Vec3) -> Vec3 {
+        let sky = self.sky_color(direction);
+        if self.has_sun() && direction.dot(self.sun_direction) >= self.cos_sun_radius {
+            sky + self.sun_radiance * self.intensity
+        }
+        else {
+            sky
+        }
+    }
+
+    //Only the sun is sampled directly, the rest of the sky is smooth enough for BSDF sampling
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        if !self.has_sun() || direction.dot(self.sun_direction) < self.cos_sun_radius {
+            return 0.0;
+        }
+        sphere_cone_pdf(self.sun_direction, self.sun_radius, Vec3::zero())
+    }
+
+    pub fn sample(&self) -> Option<(Vec3, f32)> {
+        if !self.has_sun() {
+            return None;
+        }
+        let direction = sphere_cone_direction(self.sun_direction, self.sun_radius, Vec3::zero()).normalize();
+        Some((direction, sphere_cone_pdf(self.sun_direction, self.sun_radius, Vec3::zero())))
+    }
+}
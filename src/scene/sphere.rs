@@ -1,14 +1,64 @@
 use crate::scene::{Hitable, MaterialID, HitRecord, HitableID};
-use crate::math::Ray;
+use crate::math::{Ray, to_world};
+use crate::rand::{thread_rng, Rng};
 use crate::glam::Vec3;
 
+//u runs around the equator starting at -x, v from the bottom pole to the top, direction must be normalized
+#[inline]
+pub fn sphere_uv(direction: Vec3) -> (f32, f32) {
+    let phi = (-direction.z()).atan2(direction.x()) + std::f32::consts::PI;
+    let theta = (-direction.y()).clamp(-1.0, 1.0).acos();
+    (phi / (2.0 * std::f32::consts::PI), theta / std::f32::consts::PI)
+}
+
+//How far a point on a sphere of radius moves per unit of u and of v, both vanish at the poles
+#[inline]
+pub fn sphere_derivatives(direction: Vec3, radius: f32) -> (Vec3, Vec3) {
+    let pi = std::f32::consts::PI;
+    let dpdu = Vec3::new(direction.z(), 0.0, -direction.x()) * (2.0 * pi * radius);
+    let ring = (direction.x() * direction.x() + direction.z() * direction.z()).sqrt();
+    if ring < 1e-8 {
+        return (dpdu, Vec3::zero());
+    }
+    //Up the meridian toward the top pole
+    let dpdv = Vec3::new(-direction.x() * direction.y() / ring, ring, -direction.z() * direction.y() / ring) * (pi * radius);
+    (dpdu, dpdv)
+}
+
+//Spheres are sampled over the cone they cover as seen from origin, which is uniform in solid angle
+#[inline]
+pub fn sphere_cone_pdf(center: Vec3, radius: f32, origin: Vec3) -> f32 {
+    let distance_squared = (center - origin).dot(center - origin);
+    if distance_squared <= radius * radius {
+        return 0.0;
+    }
+    let cos_max = (1.0 - radius * radius / distance_squared).sqrt();
+    1.0 / (2.0 * std::f32::consts::PI * (1.0 - cos_max))
+}
+
+#[inline]
+pub fn sphere_cone_direction(center: Vec3, radius: f32, origin: Vec3) -> Vec3 {
+    let direction = center - origin;
+    let distance_squared = direction.dot(direction);
+    if distance_squared <= radius * radius {
+        return direction;
+    }
+    let mut rng = thread_rng();
+    let r1 : f32 = rng.gen_range(0.0, 1.0);
+    let r2 : f32 = rng.gen_range(0.0, 1.0);
+    let cos_max = (1.0 - radius * radius / distance_squared).sqrt();
+    let z = 1.0 + r2 * (cos_max - 1.0);
+    let phi = 2.0 * std::f32::consts::PI * r1;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+    to_world(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z), direction.normalize())
+}
+
 pub struct Sphere  {
     center:Vec3,
     radius: f32,
     material_id: MaterialID,
     radius_sqrd: f32,
-    id: HitableID,
-    node_index: usize
+    id: HitableID
 }
 
 impl Sphere {
@@ -18,43 +68,43 @@ impl Sphere {
             radius,
             material_id,
             radius_sqrd: radius * radius,
-            id: 0,
-            node_index: 0
+            id: 0
         }
     }
 }
 
 impl Hitable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
-        
         let oc = ray.get_origin() - self.center;
         let a = ray.direction.dot(ray.direction);
         let b = oc.dot(ray.direction);
         let c = oc.dot(oc) - self.radius_sqrd;
         let d = b * b - a * c;
-        if d > 0.0 {
-            let d = d.sqrt();
-            let temp = (-b - d) / a;
-            if temp < t_max && temp > t_min {
-                record.material = self.material_id;
-                record.t = temp;
-                record.position = ray.point_at_paramater(record.t);
-                record.normal = (record.position - self.center) / self.radius;
-                record.hitable = self.id;
-                return true;
-            }
+        if d <= 0.0 {
+            return false;
+        }
 
-            let temp = (-b + d) / a;
-            if temp < t_max && temp > t_min {
-                record.material = self.material_id;
-                record.t = temp;
-                record.position = ray.point_at_paramater(record.t);
-                record.normal = (record.position - self.center) / self.radius;
-                record.hitable = self.id;
-                return true;
+        let d = d.sqrt();
+        let mut t = (-b - d) / a;
+        if t >= t_max || t <= t_min {
+            t = (-b + d) / a;
+            if t >= t_max || t <= t_min {
+                return false;
             }
         }
-        return false;
+
+        record.material = self.material_id;
+        record.t = t;
+        record.position = ray.point_at_paramater(t);
+        record.normal = (record.position - self.center) / self.radius;
+        record.hitable = self.id;
+        let direction = (record.position - self.center) / self.radius.abs();
+        let (u, v) = sphere_uv(direction);
+        record.u = u;
+        record.v = v;
+        let (dpdu, dpdv) = sphere_derivatives(direction, self.radius.abs());
+        record.set_tangent_frame(dpdu, dpdv);
+        true
     }
 
     #[inline]
@@ -78,4 +128,20 @@ impl Hitable for Sphere {
     fn get_radius(&self) -> f32 {
         return self.radius;
     }
-}
\ No newline at end of file
+
+    fn material_id(&self) -> Option<MaterialID> {
+        Some(self.material_id)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        let mut record = HitRecord::empty();
+        if !self.hit(&Ray::new_at_time(origin, direction, time), 0.001, f32::MAX, &mut record) {
+            return 0.0;
+        }
+        sphere_cone_pdf(self.center, self.radius.abs(), origin)
+    }
+
+    fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+        sphere_cone_direction(self.center, self.radius.abs(), origin)
+    }
+}
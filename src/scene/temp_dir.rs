@@ -0,0 +1,28 @@
+use std::fs;
+use std::path::PathBuf;
+
+//A directory of its own for a test's files, so tests running side by side do not share any.
+//It is removed with everything in it when the test ends, whether it passed or not.
+pub struct TempDir {
+    path: PathBuf
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("mars_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir {
+            path
+        }
+    }
+
+    pub fn join(&self, file: &str) -> PathBuf {
+        self.path.join(file)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
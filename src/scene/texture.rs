@@ -0,0 +1,273 @@
+use crate::glam::Vec3;
+use crate::math::Perlin;
+
+use std::path::Path;
+use std::sync::Arc;
+
+//A color that varies over a surface, looked up with the hit's uv and world position
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f32, v: f32, position: Vec3) -> Vec3;
+}
+
+pub struct ConstantTexture {
+    pub color: Vec3
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vec3) -> ConstantTexture {
+        ConstantTexture {
+            color
+        }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _u: f32, _v: f32, _position: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CheckerSpace {
+    //Cubes through world space, the pattern cuts through objects like they were carved from it
+    Solid,
+    //Squares laid out over the surface's uv
+    Uv
+}
+
+//Whether a point falls on an even cell of a checker pattern, scale is the number of checks per unit along each axis,
+//x and y of it run along u and v for uv checks
+#[inline]
+pub fn checker_is_even(u: f32, v: f32, position: Vec3, scale: Vec3, space: CheckerSpace) -> bool {
+    let cells = match space {
+        CheckerSpace::Solid => {
+            let p = position * scale;
+            p.x().floor() + p.y().floor() + p.z().floor()
+        },
+        CheckerSpace::Uv => (u * scale.x()).floor() + (v * scale.y()).floor()
+    };
+    (cells as i64).rem_euclid(2) == 0
+}
+
+//Alternates between two textures, scale is the number of checks per world unit or per uv unit
+pub struct CheckerTexture {
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    pub scale: f32,
+    pub space: CheckerSpace
+}
+
+impl CheckerTexture {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f32, space: CheckerSpace) -> CheckerTexture {
+        CheckerTexture {
+            even,
+            odd,
+            scale,
+            space
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f32, v: f32, position: Vec3) -> Vec3 {
+        if checker_is_even(u, v, position, Vec3::one() * self.scale, self.space) {
+            self.even.value(u, v, position)
+        }
+        else {
+            self.odd.value(u, v, position)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp
+}
+
+impl WrapMode {
+    #[inline]
+    fn wrap(self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                let period = index.rem_euclid(size * 2);
+                if period < size { period } else { size * 2 - 1 - period }
+            },
+            WrapMode::Clamp => index.max(0).min(size - 1)
+        };
+        wrapped as usize
+    }
+}
+
+//Image file sampled with bilinear filtering, v = 0 is the bottom row of the image.
+//Pixels are used as stored, the same way the renderer writes them back out.
+pub struct ImageTexture {
+    pixels: Vec<Vec3>,
+    width: usize,
+    height: usize,
+    pub wrap: WrapMode
+}
+
+impl ImageTexture {
+    pub fn new(pixels: Vec<Vec3>, width: usize, height: usize, wrap: WrapMode) -> ImageTexture {
+        ImageTexture {
+            pixels,
+            width,
+            height,
+            wrap
+        }
+    }
+
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<ImageTexture, String> {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.to_rgb();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if width == 0 || height == 0 {
+            return Err(format!("{}: image is empty", path.display()));
+        }
+
+        let pixels = image.pixels().map(|p| Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32) / 255.0).collect();
+        Ok(ImageTexture::new(pixels, width, height, wrap))
+    }
+
+    #[inline]
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _position: Vec3) -> Vec3 {
+        //Texel centers sit half a texel in from the edges
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NoiseStyle {
+    //Plain noise remapped to 0 to 1
+    Smooth,
+    //Several octaves summed, cloudy
+    Turbulence,
+    //Turbulence bending sine stripes along z
+    Marble
+}
+
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pub color: Vec3,
+    pub scale: f32,
+    pub style: NoiseStyle
+}
+
+impl NoiseTexture {
+    pub fn new(color: Vec3, scale: f32, style: NoiseStyle) -> NoiseTexture {
+        NoiseTexture {
+            perlin: Perlin::new(0),
+            color,
+            scale,
+            style
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, position: Vec3) -> Vec3 {
+        let p = position * self.scale;
+        let amount = match self.style {
+            NoiseStyle::Smooth => 0.5 * (1.0 + self.perlin.noise(p)),
+            NoiseStyle::Turbulence => self.perlin.turbulence(p, 7),
+            NoiseStyle::Marble => 0.5 * (1.0 + (p.z() + 10.0 * self.perlin.turbulence(p, 7)).sin())
+        };
+        self.color * amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::CheckerBoard;
+
+    #[test]
+    fn uv_checks_flip_across_one_check() {
+        let scale = Vec3::new(4.0, 2.0, 1.0);
+        let even = checker_is_even(0.1, 0.1, Vec3::zero(), scale, CheckerSpace::Uv);
+        //One check is a quarter along u and half along v
+        assert_ne!(checker_is_even(0.35, 0.1, Vec3::zero(), scale, CheckerSpace::Uv), even);
+        assert_ne!(checker_is_even(0.1, 0.6, Vec3::zero(), scale, CheckerSpace::Uv), even);
+        assert_eq!(checker_is_even(0.35, 0.6, Vec3::zero(), scale, CheckerSpace::Uv), even);
+        //Position plays no part in uv checks
+        assert_eq!(checker_is_even(0.1, 0.1, Vec3::new(0.7, 3.2, -1.5), scale, CheckerSpace::Uv), even);
+    }
+
+    #[test]
+    fn solid_checks_flip_across_one_check() {
+        let scale = Vec3::one() * 2.0;
+        let p = Vec3::new(0.2, -0.3, 1.1);
+        let even = checker_is_even(0.0, 0.0, p, scale, CheckerSpace::Solid);
+        for step in &[Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 0.0, 0.5)] {
+            assert_ne!(checker_is_even(0.0, 0.0, p + *step, scale, CheckerSpace::Solid), even);
+        }
+        //Negative coordinates keep alternating instead of mirroring around 0
+        assert_ne!(checker_is_even(0.0, 0.0, Vec3::new(-0.2, 0.1, 0.1), scale, CheckerSpace::Solid), checker_is_even(0.0, 0.0, Vec3::new(0.2, 0.1, 0.1), scale, CheckerSpace::Solid));
+        //uv plays no part in solid checks
+        assert_eq!(checker_is_even(0.9, 0.4, p, scale, CheckerSpace::Solid), even);
+    }
+
+    #[test]
+    fn checkerboard_has_five_by_ten_uv_checks() {
+        let board = CheckerBoard::new(Vec3::one());
+        assert!(board.space == CheckerSpace::Uv);
+        let even = checker_is_even(0.05, 0.05, Vec3::zero(), board.scale, board.space);
+        assert_ne!(checker_is_even(0.25, 0.05, Vec3::zero(), board.scale, board.space), even);
+        assert_ne!(checker_is_even(0.05, 0.15, Vec3::zero(), board.scale, board.space), even);
+        assert_eq!(checker_is_even(0.15, 0.05, Vec3::zero(), board.scale, board.space), even);
+    }
+
+    //Two by two image, the top row is red and green and the bottom row blue and white
+    fn image(wrap: WrapMode) -> ImageTexture {
+        let pixels = vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::one()];
+        ImageTexture::new(pixels, 2, 2, wrap)
+    }
+
+    #[test]
+    fn bilinear_filtering_returns_texels_at_their_centers() {
+        let texture = image(WrapMode::Repeat);
+        assert_eq!(texture.value(0.25, 0.75, Vec3::zero()), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(texture.value(0.75, 0.75, Vec3::zero()), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(texture.value(0.25, 0.25, Vec3::zero()), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(texture.value(0.75, 0.25, Vec3::zero()), Vec3::one());
+        //Halfway between red and green
+        assert_eq!(texture.value(0.5, 0.75, Vec3::zero()), Vec3::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn wrap_modes_outside_the_unit_square() {
+        let repeat = image(WrapMode::Repeat);
+        assert_eq!(repeat.value(1.25, 0.75, Vec3::zero()), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(repeat.value(-0.25, 0.75, Vec3::zero()), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(repeat.value(0.25, 1.25, Vec3::zero()), Vec3::new(0.0, 0.0, 1.0));
+
+        let clamp = image(WrapMode::Clamp);
+        assert_eq!(clamp.value(1.25, 0.75, Vec3::zero()), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(clamp.value(-3.0, 0.75, Vec3::zero()), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(clamp.value(0.25, 1.25, Vec3::zero()), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(clamp.value(0.75, -2.0, Vec3::zero()), Vec3::one());
+
+        let mirror = image(WrapMode::Mirror);
+        assert_eq!(mirror.value(1.25, 0.75, Vec3::zero()), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mirror.value(-0.25, 0.75, Vec3::zero()), Vec3::new(1.0, 0.0, 0.0));
+    }
+}